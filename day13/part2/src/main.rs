@@ -1,35 +1,11 @@
+use std::env;
 use std::fs::File;
 use std::io::Read;
 use std::collections::HashMap;
-use std::thread;
-use std::sync::mpsc;
+use std::process;
+use intcode::{Machine, Recorder, StopReason, VmError};
 use std::cmp::{max,min};
 use std::fmt;
-use std::str;
-
-#[derive(PartialEq, Debug)]
-enum CommandType {
-    ScreenOutput,
-    JoyStickRequest
-}
-
-#[derive(Debug)]
-enum ParameterMode {
-    Position,
-    Immediate,
-    Relative
-}
-
-impl From<char> for ParameterMode {
-    fn from(num: char) -> ParameterMode {
-        match num {
-            '0' => ParameterMode::Position,
-            '1' => ParameterMode::Immediate,
-            '2' => ParameterMode::Relative,
-            _ => unreachable!()
-        }
-    }
-}
 
 #[derive(PartialEq, Hash, Clone, Debug)]
 struct Position{
@@ -98,132 +74,48 @@ impl fmt::Display for TileType {
     }
 }
 
-fn get_value(h: &HashMap<i64, i64>, i: i64, p: &ParameterMode, relative_base: i64) -> i64 {
-    match p {
-        ParameterMode::Position => {
-            *h.get(h.get(&i).unwrap()).or(Some(&0)).unwrap()
-        },
-        ParameterMode::Immediate => {
-            *h.get(&i).or(Some(&0)).unwrap()
-        },
-        ParameterMode::Relative => {
-            *h.get(&(*h.get(&i).or(Some(&0)).unwrap() + relative_base)).or(Some(&0)).unwrap()
-        }
-    }
+/// Plain machine, or one wrapped in a `Recorder` when `--record` was given
+enum Runner {
+    Plain(Machine),
+    Recording(Recorder)
 }
 
-fn store_value(h: &mut HashMap<i64, i64>, i: i64, value: i64, p: &ParameterMode, relative_base: i64) {
-    match p {
-        ParameterMode::Position => {
-            h.insert(*h.get(&i).or(Some(&0)).unwrap(), value);
-        },
-        ParameterMode::Relative => {
-            h.insert(*h.get(&i).or(Some(&0)).unwrap() + relative_base, value);
-        },
-        _ => unreachable!() // immediate never given for storing values
+impl Runner {
+    fn new(machine: Machine, record: bool) -> Self {
+        if record {
+            Runner::Recording(Recorder::new(machine))
+        } else {
+            Runner::Plain(machine)
+        }
     }
-}
-
-fn run_game(h: &mut HashMap<i64, i64>, output_channel: mpsc::Sender<i64>, input_channel: mpsc::Receiver<i64>, command_channel: mpsc::Sender<CommandType>) {
-    let mut current_op_index: i64 = 0;
-    let mut relative_base = 0;
 
-    while current_op_index < h.len() as i64 {
-        let op  = *h.get(&current_op_index).unwrap();
-        let mut op_digits: Vec<char> = op.to_string().chars().collect();
-
-        while op_digits.len() < 5 {
-            op_digits.insert(0,'0');
+    fn push_input(&mut self, value: i64) {
+        match self {
+            Runner::Plain(m) => m.push_input(value),
+            Runner::Recording(r) => r.push_input(value),
         }
+    }
 
-
-        let mut op_code = String::new();
-        op_code.push(op_digits[3]);
-        op_code.push(op_digits[4]);
-
-        let param_first = ParameterMode::from(op_digits[2]);
-        let param_second = ParameterMode::from(op_digits[1]);
-        let param_third = ParameterMode::from(op_digits[0]);
-
-        if op_code == "01" {
-            let first_value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let second_value = get_value(&h, current_op_index+2, &param_second, relative_base);
-
-            store_value(h, current_op_index+3, first_value+second_value, &param_third, relative_base);
-            current_op_index += 4;
-        } else if op_code == "02" {
-            let first_value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let second_value = get_value(&h, current_op_index+2, &param_second, relative_base);
-
-            store_value(h, current_op_index+3, first_value*second_value, &param_third, relative_base);
-            current_op_index += 4;
-        } else if op_code == "03" {
-            command_channel.send(CommandType::JoyStickRequest).unwrap();
-            let joystick_value = input_channel.recv().unwrap();
-
-            store_value(h, current_op_index+1, joystick_value, &param_first, relative_base);
-            current_op_index += 2;
-        } else if op_code == "04" {
-            command_channel.send(CommandType::ScreenOutput).unwrap();
-
-            let value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let _ = output_channel.send(value);
-            current_op_index += 2;
-        } else if op_code == "05" {
-            let first_value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let second_value = get_value(&h, current_op_index+2, &param_second, relative_base);
-
-            if first_value != 0 {
-                current_op_index = second_value;
-            } else {
-                current_op_index += 3;
-            }
-        } else if op_code == "06" {
-            let first_value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let second_value = get_value(&h, current_op_index+2, &param_second, relative_base);
-
-            if first_value == 0 {
-                current_op_index = second_value;
-            } else {
-                current_op_index += 3;
-            }
-        } else if op_code == "07" {
-            let first_value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let second_value = get_value(&h, current_op_index+2, &param_second, relative_base);
-
-            if first_value < second_value {
-                store_value(h, current_op_index+3, 1, &param_third, relative_base);
-            } else {
-                store_value(h, current_op_index+3, 0, &param_third, relative_base);
-            }
-
-            current_op_index += 4;
-        } else if op_code == "08" {
-            let first_value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let second_value = get_value(&h, current_op_index+2, &param_second, relative_base);
-
-            if first_value == second_value {
-                store_value(h, current_op_index+3, 1, &param_third, relative_base);
-            } else {
-                store_value(h, current_op_index+3, 0, &param_third, relative_base);
-            }
-
-            current_op_index += 4;
-        } else if op_code == "09" {
-            let value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            relative_base += value;
-            current_op_index += 2;
-        } else if op_code == "99" {
-            return;
+    fn run(&mut self) -> Result<StopReason, VmError> {
+        match self {
+            Runner::Plain(m) => m.run(),
+            Runner::Recording(r) => r.run(),
         }
     }
-    panic!("Didn't get halt opcode 99");
 }
 
+fn next_output(m: &mut Runner) -> i64 {
+    match m.run() {
+        Ok(StopReason::Output(value)) => value,
+        r => panic!("expected output, got {:?}", r)
+    }
+}
+
+#[allow(dead_code)] // only used when uncommented in main
 fn display_image(i: &HashMap<Position, TileType>, max_x: i64, max_y: i64) {
     for y in 0..max_y {
         for x in 0..max_x {
-            let t = i.get(&Position{x,y}).or(Some(&TileType::Empty)).unwrap();
+            let t = i.get(&Position{x,y}).unwrap_or(&TileType::Empty);
             print!("{}", t);
         }
         println!();
@@ -231,23 +123,24 @@ fn display_image(i: &HashMap<Position, TileType>, max_x: i64, max_y: i64) {
 }
 
 fn main() {
+    // `--record <file>` saves the game for `intcode-replay`
+    let args: Vec<String> = env::args().skip(1).collect();
+    let record = match &args[..] {
+        [] => None,
+        [flag, path] if flag == "--record" => Some(path.clone()),
+        _ => {
+            eprintln!("usage: day13-part2 [--record <file>]");
+            process::exit(2);
+        }
+    };
+
     let mut input = File::open("../input.txt").expect("couldn't open input");
     let mut s = String::new();
     let _ = input.read_to_string(&mut s);
 
-    let mut h: HashMap<i64, i64> = HashMap::new();
-    for (i, s) in s.trim_end().split(',').collect::<Vec<&str>>().iter().enumerate() {
-        h.insert(i as i64, s.parse().unwrap());
-    }
-    h.insert(0i64, 2); // initial value to start part 2
-
-    let (screen_tx, screen_rx) = mpsc::channel();
-    let (joystick_tx, joystick_rx) = mpsc::channel();
-    let (command_tx, command_rx) = mpsc::channel();
-
-    thread::spawn(move || {
-        run_game(&mut h, screen_tx, joystick_rx, command_tx);
-    });
+    let mut machine = Machine::new(&intcode::parse_program(&s));
+    machine.write(0, 2); // initial value to start part 2
+    let mut m = Runner::new(machine, record.is_some());
 
     let mut game_state: HashMap<Position, TileType> = HashMap::new();
     let mut min_x = 0;
@@ -257,67 +150,63 @@ fn main() {
     let mut last_score = 0;
 
     loop {
-        match command_rx.recv() {
-            Ok(command) => match command {
-                CommandType::ScreenOutput => {
-                    let x_value = screen_rx.recv().unwrap();
-                    assert_eq!(command_rx.recv().unwrap(), CommandType::ScreenOutput);
-                    let y_value = screen_rx.recv().unwrap();
-                    assert_eq!(command_rx.recv().unwrap(), CommandType::ScreenOutput);
-
-                    if x_value == -1 && y_value == 0 {
-                        let score = screen_rx.recv().unwrap();
-                        last_score = score;
-//                        println!("Score: {}", score);
-                    } else {
-                        let tile_type: TileType = screen_rx.recv().unwrap().into();
-                        game_state.insert(Position { x: x_value, y: y_value }, tile_type);
-
-                        min_x = min(min_x, x_value);
-                        min_y = min(min_y, y_value);
-                        max_x = max(max_x, x_value);
-                        max_y = max(max_y, y_value);
+        match m.run().unwrap() {
+            StopReason::Output(x_value) => {
+                let y_value = next_output(&mut m);
+
+                if x_value == -1 && y_value == 0 {
+                    let score = next_output(&mut m);
+                    last_score = score;
+//                    println!("Score: {}", score);
+                } else {
+                    let tile_type: TileType = next_output(&mut m).into();
+                    game_state.insert(Position { x: x_value, y: y_value }, tile_type);
+
+                    min_x = min(min_x, x_value);
+                    min_y = min(min_y, y_value);
+                    max_x = max(max_x, x_value);
+                    max_y = max(max_y, y_value);
+                }
+            },
+            StopReason::NeedInput => {
+                // draw screen and send joystick input
+//                display_image(&game_state, max_x, max_y); // uncomment to see game in console
+//                std::thread::sleep(std::time::Duration::from_millis(250)); // uncomment to have game progress at constant pace automatically
+//                std::io::stdin().read(&mut [0u8]).unwrap(); // uncomment to pause for user // input to see screen
+                let mut paddle_pos = None;
+                let mut ball_pos = None;
+                for (pos, t) in &game_state {
+                    if let TileType::Ball = t {
+                        ball_pos = Some(pos.clone());
+                    } else if let TileType::HorizontalPaddle = t {
+                        paddle_pos = Some(pos.clone());
                     }
-                },
-                CommandType::JoyStickRequest => {
-                    // draw screen and send joystick input
-//                    display_image(&game_state, max_x, max_y); // uncomment to see game in console
-//                    std::thread::sleep(std::time::Duration::from_millis(250)); // uncomment to have game progress at constant pace automatically
-//                    std::io::stdin().read(&mut [0u8]).unwrap(); // uncomment to pause for user // input to see screen
-                    let mut paddle_pos = None;
-                    let mut ball_pos = None;
-                    for (pos, t) in &game_state {
-                        if let TileType::Ball = t {
-                            ball_pos = Some(pos.clone());
-                        } else if let TileType::HorizontalPaddle = t {
-                            paddle_pos = Some(pos.clone());
-                        }
 
-                        if ball_pos.is_some() && paddle_pos.is_some() {
-                            break;
-                        }
+                    if ball_pos.is_some() && paddle_pos.is_some() {
+                        break;
                     }
+                }
 
-                    let paddle_pos = paddle_pos.unwrap();
-                    let ball_pos = ball_pos.unwrap();
-
-                    if paddle_pos.x < ball_pos.x {
-                        // move right
-                        let _ = joystick_tx.send(1);
-                    } else if paddle_pos.x > ball_pos.x {
-                        // move left
-                        let _ = joystick_tx.send(-1);
-                    } else {
-                        // don't move
-                        let _ = joystick_tx.send(0);
-                    }
+                let paddle_pos = paddle_pos.unwrap();
+                let ball_pos = ball_pos.unwrap();
+
+                if paddle_pos.x < ball_pos.x {
+                    // move right
+                    m.push_input(1);
+                } else if paddle_pos.x > ball_pos.x {
+                    // move left
+                    m.push_input(-1);
+                } else {
+                    // don't move
+                    m.push_input(0);
                 }
             },
-            Err(_) => {
-                break;
-            }
+            StopReason::Halted => break
         }
     }
 
+    if let (Some(path), Runner::Recording(recorder)) = (record, &m) {
+        recorder.session().save(path).expect("couldn't save the session");
+    }
     println!("{}", last_score);
 }
\ No newline at end of file
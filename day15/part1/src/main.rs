@@ -1,39 +1,21 @@
+use std::env;
 use std::fs::File;
 use std::io::Read;
 use std::collections::HashMap;
-use std::thread;
-use std::sync::mpsc;
+use std::process;
+use intcode::{Machine, Recorder, StopReason, VmError};
 use std::cmp::{min,max};
 use std::fmt;
 
 extern crate pathfinding;
-use pathfinding::utils::absdiff;
 use pathfinding::prelude::{astar, bfs};
 
-#[derive(Debug)]
-enum ParameterMode {
-    Position,
-    Immediate,
-    Relative
-}
-
-impl From<char> for ParameterMode {
-    fn from(num: char) -> ParameterMode {
-        match num {
-            '0' => ParameterMode::Position,
-            '1' => ParameterMode::Immediate,
-            '2' => ParameterMode::Relative,
-            _ => unreachable!()
-        }
-    }
-}
-
 #[derive(Debug, Eq, Ord, PartialOrd, PartialEq, Hash, Clone, Copy)]
 struct Position(i64,i64);
 
 impl Position {
     fn distance(&self, other: &Self) -> u32 {
-        absdiff(self.0, other.0) as u32 + absdiff(self.1, other.1) as u32
+        ((self.0 - other.0).abs() + (self.1 - other.1).abs()) as u32
     }
 
     fn move_to_other(&self, other: &Self) -> MovementCommand {
@@ -74,7 +56,7 @@ impl Grid {
             max_y: 0
         };
         g.known_positions.insert(Position(0,0), StatusCode::Ok);
-        return g;
+        g
     }
 
     fn successors(&self, p: &Position) -> Vec<(Position, u32)> {
@@ -84,28 +66,28 @@ impl Grid {
 
         { // scopes to prevent accidentally using same position twice when copy/pasting
             let up_pos = Position( p.0, p.1 + 1 );
-            if *self.known_positions.get(&up_pos).or(Some(&StatusCode::Unknown)).unwrap() != StatusCode::Wall {
+            if *self.known_positions.get(&up_pos).unwrap_or(&StatusCode::Unknown) != StatusCode::Wall {
                 rv.push((up_pos, 1));
             }
         }
 
         {
             let down_pos = Position( p.0, p.1 - 1 );
-            if *self.known_positions.get(&down_pos).or(Some(&StatusCode::Unknown)).unwrap() != StatusCode::Wall {
+            if *self.known_positions.get(&down_pos).unwrap_or(&StatusCode::Unknown) != StatusCode::Wall {
                 rv.push((down_pos, 1));
             }
         }
 
         {
             let left_pos = Position( p.0 - 1, p.1 );
-            if *self.known_positions.get(&left_pos).or(Some(&StatusCode::Unknown)).unwrap() != StatusCode::Wall {
+            if *self.known_positions.get(&left_pos).unwrap_or(&StatusCode::Unknown) != StatusCode::Wall {
                 rv.push((left_pos, 1));
             }
         }
 
         {
             let right_pos = Position( p.0 + 1, p.1 );
-            if *self.known_positions.get(&right_pos).or(Some(&StatusCode::Unknown)).unwrap() != StatusCode::Wall {
+            if *self.known_positions.get(&right_pos).unwrap_or(&StatusCode::Unknown) != StatusCode::Wall {
                 rv.push((right_pos, 1));
             }
         }
@@ -113,21 +95,24 @@ impl Grid {
         rv
     }
 
-    fn move_robot_to_target(&mut self, target: &Position, robot_tx: &mpsc::Sender<i64>, robot_rx: &mpsc::Receiver<i64>, draw: bool) -> StatusCode {
+    fn move_robot_to_target(&mut self, target: &Position, m: &mut Runner, draw: bool) -> StatusCode {
         let mut s: StatusCode;
 
         'find_target: loop {
-            let path = astar(&self.robot_position, |p| self.successors(p), |p| p.distance(&target), |p| p == target).unwrap();
+            let path = astar(&self.robot_position, |p| self.successors(p), |p| p.distance(target), |p| p == target).unwrap();
 
             // if there are any unknowns, can only move to first one before recalculating path
             // because revealing can show a path is no longer valid
             for path_pos in path.0.iter().skip(1) {
                 let movement = self.robot_position.move_to_other(path_pos);
-                robot_tx.send(movement.into()).unwrap();
-                s = robot_rx.recv().unwrap().into();
+                m.push_input(movement.into());
+                s = match m.run() {
+                    Ok(StopReason::Output(status)) => status.into(),
+                    r => panic!("expected status code, got {:?}", r)
+                };
 
                 if s != StatusCode::Wall {
-                    self.robot_position = path_pos.clone();
+                    self.robot_position = *path_pos;
                 }
 
                 if draw {
@@ -135,8 +120,8 @@ impl Grid {
 //                    std::io::stdin().read(&mut [0u8]).unwrap();
                 }
 
-                if self.known_positions.get(&path_pos).is_none() {
-                    self.known_positions.insert(path_pos.clone(), s.clone());
+                if !self.known_positions.contains_key(path_pos) {
+                    self.known_positions.insert(*path_pos, s.clone());
                     self.min_x = min(self.min_x, path_pos.0);
                     self.min_y = min(self.min_y, path_pos.1);
                     self.max_x = max(self.max_x, path_pos.0);
@@ -168,7 +153,7 @@ impl Grid {
                 } else if x == 0 && y == 0 {
                     print!("X")
                 } else {
-                    print!("{:?}", self.known_positions.get(&Position(x, y)).or(Some(&StatusCode::Unknown)).unwrap());
+                    print!("{:?}", self.known_positions.get(&Position(x, y)).unwrap_or(&StatusCode::Unknown));
                 }
             }
             println!();
@@ -186,9 +171,9 @@ enum MovementCommand {
     West
 }
 
-impl Into<i64> for MovementCommand {
-    fn into(self) -> i64 {
-        match self {
+impl From<MovementCommand> for i64 {
+    fn from(val: MovementCommand) -> Self {
+        match val {
             MovementCommand::North => 1,
             MovementCommand::South => 2,
             MovementCommand::West => 3,
@@ -238,145 +223,53 @@ impl From<i64> for StatusCode {
     }
 }
 
-fn get_value(h: &HashMap<i64, i64>, i: i64, p: &ParameterMode, relative_base: i64) -> i64 {
-    match p {
-        ParameterMode::Position => {
-            *h.get(h.get(&i).unwrap()).or(Some(&0)).unwrap()
-        },
-        ParameterMode::Immediate => {
-            *h.get(&i).or(Some(&0)).unwrap()
-        },
-        ParameterMode::Relative => {
-            *h.get(&(*h.get(&i).or(Some(&0)).unwrap() + relative_base)).or(Some(&0)).unwrap()
-        }
-    }
+/// Plain machine, or one wrapped in a `Recorder` when `--record` was given
+enum Runner {
+    Plain(Machine),
+    Recording(Recorder)
 }
 
-fn store_value(h: &mut HashMap<i64, i64>, i: i64, value: i64, p: &ParameterMode, relative_base: i64) {
-    match p {
-        ParameterMode::Position => {
-            h.insert(*h.get(&i).or(Some(&0)).unwrap(), value);
-        },
-        ParameterMode::Relative => {
-            h.insert(*h.get(&i).or(Some(&0)).unwrap() + relative_base, value);
-        },
-        _ => unreachable!() // immediate never given for storing values
+impl Runner {
+    fn new(machine: Machine, record: bool) -> Self {
+        if record {
+            Runner::Recording(Recorder::new(machine))
+        } else {
+            Runner::Plain(machine)
+        }
     }
-}
-
-fn run_robot(h: &mut HashMap<i64, i64>, input: mpsc::Receiver<i64>, output: mpsc::Sender<i64>) {
-    let mut current_op_index: i64 = 0;
-    let mut relative_base = 0;
 
-    while current_op_index < h.len() as i64 {
-        let op  = *h.get(&current_op_index).unwrap();
-        let mut op_digits: Vec<char> = op.to_string().chars().collect();
-
-        while op_digits.len() < 5 {
-            op_digits.insert(0,'0');
+    fn push_input(&mut self, value: i64) {
+        match self {
+            Runner::Plain(m) => m.push_input(value),
+            Runner::Recording(r) => r.push_input(value),
         }
+    }
 
-
-        let mut op_code = String::new();
-        op_code.push(op_digits[3]);
-        op_code.push(op_digits[4]);
-
-        let param_first = ParameterMode::from(op_digits[2]);
-        let param_second = ParameterMode::from(op_digits[1]);
-        let param_third = ParameterMode::from(op_digits[0]);
-
-        if op_code == "01" {
-            let first_value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let second_value = get_value(&h, current_op_index+2, &param_second, relative_base);
-
-            store_value(h, current_op_index+3, first_value+second_value, &param_third, relative_base);
-            current_op_index += 4;
-        } else if op_code == "02" {
-            let first_value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let second_value = get_value(&h, current_op_index+2, &param_second, relative_base);
-
-            store_value(h, current_op_index+3, first_value*second_value, &param_third, relative_base);
-            current_op_index += 4;
-        } else if op_code == "03" {
-            let value = input.recv();
-            if let Err(_) = value {
-                // program over, kill robot sim thread
-                return;
-            } else {
-                store_value(h, current_op_index + 1, value.unwrap(), &param_first, relative_base);
-                current_op_index += 2;
-            }
-        } else if op_code == "04" {
-            let value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let _ = output.send(value);
-            current_op_index += 2;
-        } else if op_code == "05" {
-            let first_value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let second_value = get_value(&h, current_op_index+2, &param_second, relative_base);
-
-            if first_value != 0 {
-                current_op_index = second_value;
-            } else {
-                current_op_index += 3;
-            }
-        } else if op_code == "06" {
-            let first_value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let second_value = get_value(&h, current_op_index+2, &param_second, relative_base);
-
-            if first_value == 0 {
-                current_op_index = second_value;
-            } else {
-                current_op_index += 3;
-            }
-        } else if op_code == "07" {
-            let first_value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let second_value = get_value(&h, current_op_index+2, &param_second, relative_base);
-
-            if first_value < second_value {
-                store_value(h, current_op_index+3, 1, &param_third, relative_base);
-            } else {
-                store_value(h, current_op_index+3, 0, &param_third, relative_base);
-            }
-
-            current_op_index += 4;
-        } else if op_code == "08" {
-            let first_value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            let second_value = get_value(&h, current_op_index+2, &param_second, relative_base);
-
-            if first_value == second_value {
-                store_value(h, current_op_index+3, 1, &param_third, relative_base);
-            } else {
-                store_value(h, current_op_index+3, 0, &param_third, relative_base);
-            }
-
-            current_op_index += 4;
-        } else if op_code == "09" {
-            let value = get_value(&h, current_op_index+1, &param_first, relative_base);
-            relative_base += value;
-            current_op_index += 2;
-        } else if op_code == "99" {
-            return;
+    fn run(&mut self) -> Result<StopReason, VmError> {
+        match self {
+            Runner::Plain(m) => m.run(),
+            Runner::Recording(r) => r.run(),
         }
     }
-    panic!("Didn't get halt opcode 99");
 }
 
 fn main() {
+    // `--record <file>` saves the exploration for `intcode-replay`
+    let args: Vec<String> = env::args().skip(1).collect();
+    let record = match &args[..] {
+        [] => None,
+        [flag, path] if flag == "--record" => Some(path.clone()),
+        _ => {
+            eprintln!("usage: day15-part1 [--record <file>]");
+            process::exit(2);
+        }
+    };
+
     let mut input = File::open("../input.txt").expect("couldn't open input");
     let mut s = String::new();
     let _ = input.read_to_string(&mut s);
 
-    let mut h: HashMap<i64, i64> = HashMap::new();
-    for (i, s) in s.trim_end().split(',').collect::<Vec<&str>>().iter().enumerate() {
-        h.insert(i as i64, s.parse().unwrap());
-    }
-
-    let (robot_command_tx, robot_command_rx) = mpsc::channel();
-    let (robot_status_tx, robot_status_rx) = mpsc::channel();
-
-    thread::spawn(move || {
-        run_robot(&mut h, robot_command_rx, robot_status_tx);
-    });
+    let mut m = Runner::new(Machine::new(&intcode::parse_program(&s)), record.is_some());
 
     let mut grid = Grid::new();
 
@@ -388,11 +281,11 @@ fn main() {
         // get shortest path to unknown position
         let path = bfs(&grid.robot_position,
                          |p| grid.successors(p).iter().map(|p2| p2.0).collect::<Vec<Position>>(),
-                         |p| *grid.known_positions.get(&p).or(Some(&StatusCode::Unknown)).unwrap() == StatusCode::Unknown).unwrap();
+                         |p| *grid.known_positions.get(p).unwrap_or(&StatusCode::Unknown) == StatusCode::Unknown).unwrap();
 
         // send movement commands to reach unknown
         for path_pos in path.iter().skip(1) {
-            let s = grid.move_robot_to_target(path_pos, &robot_command_tx, &robot_status_rx, false);
+            let s = grid.move_robot_to_target(path_pos, &mut m, false);
             if s == StatusCode::Goal {
                 found_goal = true;
             }
@@ -402,7 +295,7 @@ fn main() {
         }
     }
 
-    let oxygen_position = grid.robot_position.clone();
+    let oxygen_position = grid.robot_position;
 
     // robot_position is now position of oxygen system
     // find shortest path from there back to start at 0, 0
@@ -415,21 +308,25 @@ fn main() {
         for path_pos in path.0.iter().skip(1) {
             if *path_pos == grid.start {
                 // uncomment two lines to see robot take path from start to oxygen system
-//                grid.move_robot_to_target(&oxygen_position, &robot_command_tx, &robot_status_rx, true);
-//                grid.move_robot_to_target(&grid.start.clone(), &robot_command_tx, &robot_status_rx, true);
+//                grid.move_robot_to_target(&oxygen_position, &mut m, true);
+//                grid.move_robot_to_target(&grid.start.clone(), &mut m, true);
                 println!("{}", path.1);
                 break 'find_best_to_start;
             }
 
-            let s = grid.known_positions.get(&path_pos).or(Some(&StatusCode::Unknown)).unwrap();
+            let s = grid.known_positions.get(path_pos).unwrap_or(&StatusCode::Unknown);
             if *s == StatusCode::Unknown {
-               first_unknown = Some(path_pos.clone());
+               first_unknown = Some(*path_pos);
                 break;
             }
         }
 
         let first_unknown = first_unknown.unwrap();
-        grid.move_robot_to_target(&first_unknown, &robot_command_tx, &robot_status_rx, false);
+        grid.move_robot_to_target(&first_unknown, &mut m, false);
+
+    }
 
+    if let (Some(path), Runner::Recording(recorder)) = (record, &m) {
+        recorder.session().save(path).expect("couldn't save the session");
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,51 @@
+// Compares the interpreter against day9's program translated to Rust, running the BOOST program
+// in sensor mode. Run with `cargo bench -p intcode-aot-check`.
+
+use std::time::{Duration, Instant};
+use intcode::aot::{self, Buffered};
+use intcode::{Machine, StopReason};
+use intcode_aot_check::translated;
+
+const ITERATIONS: u32 = 20;
+
+fn time<F: FnMut() -> Vec<i64>>(name: &str, mut f: F) -> Duration {
+    let expected = f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        assert_eq!(f(), expected);
+    }
+    let per_run = start.elapsed() / ITERATIONS;
+    println!("{:<24} {:>10.2?} per run", name, per_run);
+    per_run
+}
+
+fn main() {
+    let program = translated::DAY9_PROGRAM;
+
+    let machine = time("Machine + decode cache", || {
+        let mut m = Machine::new(&program);
+        m.set_decode_cache(true);
+        m.push_input(2);
+        let mut outputs = Vec::new();
+        while let StopReason::Output(value) = m.run().unwrap() {
+            outputs.push(value);
+        }
+        outputs
+    });
+    let fallback = time("aot::interpret", || {
+        let mut io = Buffered::new(&[2]);
+        aot::interpret(&program, &mut io).unwrap();
+        io.outputs
+    });
+    let translated = time("translated", || {
+        let mut io = Buffered::new(&[2]);
+        translated::day9(&mut io).unwrap();
+        io.outputs
+    });
+
+    println!(
+        "speedup over the interpreter: {:.1}x ({:.1}x over aot::interpret)",
+        machine.as_secs_f64() / translated.as_secs_f64(),
+        fallback.as_secs_f64() / translated.as_secs_f64()
+    );
+}
@@ -0,0 +1,39 @@
+// Translates every day's program with intcode2rs's translator so the tests can compare the
+// generated code against the interpreter.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const DAYS: [&str; 7] = ["day2", "day5", "day7", "day9", "day11", "day13", "day15"];
+
+// small programs that write into their own code, from day 2's examples
+const SELF_MODIFYING: [(&str, &str); 2] = [
+    ("example1", "1,9,10,3,2,3,11,0,99,30,40,50"),
+    ("example2", "1,1,1,4,99,5,6,0,99")
+];
+
+// programs that overflow the relative base, adjusting it and using it
+const OVERFLOWING: [(&str, &str); 2] = [
+    ("rb_overflow", "109,9223372036854775807,109,1,99"),
+    ("relative_overflow", "109,9223372036854775807,204,1,99")
+];
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let mut generated = String::new();
+
+    for day in DAYS.iter() {
+        let path = format!("../{}/input.txt", day);
+        println!("cargo:rerun-if-changed={}", path);
+        let program = intcode::parse_program(&fs::read_to_string(&path).expect("couldn't open input"));
+        generated.push_str(&intcode::translate(&program, day));
+        generated.push('\n');
+    }
+    for (name, program) in SELF_MODIFYING.iter().chain(OVERFLOWING.iter()) {
+        generated.push_str(&intcode::translate(&intcode::parse_program(program), name));
+        generated.push('\n');
+    }
+
+    fs::write(Path::new(&out_dir).join("translated.rs"), generated).unwrap();
+}
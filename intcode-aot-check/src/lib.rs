@@ -0,0 +1,104 @@
+//! Every day's program translated to Rust by `intcode::translate` at build time, see `build.rs`.
+//!
+//! The tests run each translation and the interpreter on the same inputs and expect the same
+//! outputs and the same error, if any.
+
+pub mod translated {
+    include!(concat!(env!("OUT_DIR"), "/translated.rs"));
+}
+
+#[cfg(test)]
+mod tests {
+    use intcode::aot::{self, AotError, Buffered};
+    use intcode::VmError;
+    use crate::translated;
+
+    type Translated = fn(&mut dyn aot::Io) -> Result<(), AotError>;
+
+    fn compare(program: &[i64], translated: Translated, inputs: &[i64]) -> Vec<i64> {
+        let mut expected = Buffered::new(inputs);
+        let expected_result = aot::interpret(program, &mut expected);
+
+        let mut actual = Buffered::new(inputs);
+        let actual_result = translated(&mut actual);
+
+        assert_eq!(actual_result, expected_result, "inputs {:?}", inputs);
+        assert_eq!(actual.outputs, expected.outputs, "inputs {:?}", inputs);
+        actual.outputs
+    }
+
+    /// A fixed pseudo random sequence of values from `choices`.
+    fn inputs(choices: &[i64], len: usize) -> Vec<i64> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        (0..len).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            choices[(state % choices.len() as u64) as usize]
+        }).collect()
+    }
+
+    #[test]
+    fn day2() {
+        compare(&translated::DAY2_PROGRAM, translated::day2, &[]);
+    }
+
+    #[test]
+    fn day5() {
+        assert_eq!(compare(&translated::DAY5_PROGRAM, translated::day5, &[1]).last(), Some(&9654885));
+        assert_eq!(compare(&translated::DAY5_PROGRAM, translated::day5, &[5]), vec![7079459]);
+        for input in &[0, 7, 8, 9, -1] {
+            compare(&translated::DAY5_PROGRAM, translated::day5, &[*input]);
+        }
+        compare(&translated::DAY5_PROGRAM, translated::day5, &[]);
+    }
+
+    #[test]
+    fn day7() {
+        for phase in 0..10 {
+            for signal in &[0, 1, 12345] {
+                compare(&translated::DAY7_PROGRAM, translated::day7, &[phase, *signal, 7, 8, 9, 10, 11, 12, 13, 14]);
+            }
+        }
+    }
+
+    #[test]
+    fn day9() {
+        assert_eq!(compare(&translated::DAY9_PROGRAM, translated::day9, &[1]), vec![2350741403]);
+        assert_eq!(compare(&translated::DAY9_PROGRAM, translated::day9, &[2]), vec![53088]);
+        compare(&translated::DAY9_PROGRAM, translated::day9, &[3]);
+    }
+
+    #[test]
+    fn day11() {
+        compare(&translated::DAY11_PROGRAM, translated::day11, &inputs(&[0, 1], 20_000));
+        compare(&translated::DAY11_PROGRAM, translated::day11, &[0; 500]);
+    }
+
+    #[test]
+    fn day13() {
+        compare(&translated::DAY13_PROGRAM, translated::day13, &[]);
+    }
+
+    #[test]
+    fn day15() {
+        compare(&translated::DAY15_PROGRAM, translated::day15, &inputs(&[1, 2, 3, 4], 20_000));
+    }
+
+    #[test]
+    fn self_modifying() {
+        compare(&translated::EXAMPLE1_PROGRAM, translated::example1, &[]);
+        compare(&translated::EXAMPLE2_PROGRAM, translated::example2, &[]);
+    }
+
+    #[test]
+    fn relative_base_overflow() {
+        let mut io = Buffered::new(&[]);
+        assert_eq!(translated::rb_overflow(&mut io), Err(AotError::Vm(VmError::Overflow { ip: 2, opcode: 109 })));
+        compare(&translated::RB_OVERFLOW_PROGRAM, translated::rb_overflow, &[]);
+
+        let mut io = Buffered::new(&[]);
+        assert_eq!(translated::relative_overflow(&mut io), Err(AotError::Vm(VmError::Overflow { ip: 2, opcode: 204 })));
+        compare(&translated::RELATIVE_OVERFLOW_PROGRAM, translated::relative_overflow, &[]);
+    }
+}
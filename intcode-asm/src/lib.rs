@@ -0,0 +1,346 @@
+//! Assembler for Intcode programs.
+//!
+//! The syntax is the one `intcode-dis` prints, plus labels and comments:
+//!
+//! ```text
+//! ; count down from 3
+//!         ADD #3, #0, [counter]
+//! loop:   OUT [counter]
+//!         ADD [counter], #-1, [counter]
+//!         JNZ [counter], #loop
+//!         HLT
+//! counter: db 0
+//! ```
+//!
+//! Operands are `[addr]` for position mode, `#value` for immediate mode and `rb+off` for
+//! relative mode. Addresses, values and offsets can be numbers, labels or sums like `label+2`.
+//! `db` (or `DATA`) emits raw words. A leading `12:` address, as in disassembler listings, is
+//! ignored.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use intcode::{Instruction, Opcode, ParameterMode};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    UnknownMnemonic(String),
+    WrongOperandCount { mnemonic: String, expected: usize, found: usize },
+    BadOperand(String),
+    BadExpression(String),
+    ImmediateWrite(String),
+    DuplicateLabel(String),
+    UndefinedLabel(String)
+}
+
+/// An assembly error and the 1-based source line it was found on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub kind: AsmErrorKind
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AsmErrorKind::UnknownMnemonic(mnemonic) => write!(f, "unknown mnemonic `{}`", mnemonic),
+            AsmErrorKind::WrongOperandCount { mnemonic, expected, found } => {
+                write!(f, "{} takes {} operands, found {}", mnemonic, expected, found)
+            },
+            AsmErrorKind::BadOperand(operand) => {
+                write!(f, "operand `{}` needs to be `[addr]`, `#value` or `rb+off`", operand)
+            },
+            AsmErrorKind::BadExpression(expression) => write!(f, "can't parse `{}`", expression),
+            AsmErrorKind::ImmediateWrite(mnemonic) => write!(f, "{} can't write to an immediate operand", mnemonic),
+            AsmErrorKind::DuplicateLabel(label) => write!(f, "label `{}` defined twice", label),
+            AsmErrorKind::UndefinedLabel(label) => write!(f, "label `{}` is never defined", label)
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Debug, Clone)]
+enum Term {
+    /// Wider than a word so that `-9223372036854775808` parses before its sign is applied.
+    Number(i128),
+    Label(String)
+}
+
+/// A sum of numbers and labels, resolved once every label's address is known. The source text is
+/// kept for errors. The sum is worked out in `i128` and only has to fit a word at the end.
+#[derive(Debug, Clone)]
+struct Expression {
+    source: String,
+    terms: Vec<(i128, Term)>
+}
+
+impl Expression {
+    fn parse(s: &str) -> Option<Expression> {
+        let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        if s.is_empty() {
+            return None;
+        }
+
+        let mut terms = Vec::new();
+        let mut sign = 1i128;
+        let mut rest = s.as_str();
+        loop {
+            if let Some(r) = rest.strip_prefix('-') {
+                sign = -sign;
+                rest = r;
+                continue;
+            }
+            if let Some(r) = rest.strip_prefix('+') {
+                rest = r;
+                continue;
+            }
+
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            let (term, r) = rest.split_at(end);
+            terms.push((sign, Term::parse(term)?));
+            sign = 1;
+            rest = r;
+            if rest.is_empty() {
+                return Some(Expression { source: s, terms });
+            }
+        }
+    }
+
+    fn resolve(&self, labels: &HashMap<String, i64>) -> Result<i64, AsmErrorKind> {
+        let bad_expression = || AsmErrorKind::BadExpression(self.source.clone());
+        let mut value = 0i128;
+        for (sign, term) in &self.terms {
+            let term_value = match term {
+                Term::Number(n) => *n,
+                Term::Label(label) => *labels.get(label).ok_or_else(|| AsmErrorKind::UndefinedLabel(label.clone()))? as i128
+            };
+            value = sign.checked_mul(term_value).and_then(|term_value| value.checked_add(term_value)).ok_or_else(bad_expression)?;
+        }
+        i64::try_from(value).map_err(|_| bad_expression())
+    }
+}
+
+impl Term {
+    fn parse(s: &str) -> Option<Term> {
+        if let Ok(n) = s.parse() {
+            Some(Term::Number(n))
+        } else if is_label(s) {
+            Some(Term::Label(s.to_string()))
+        } else {
+            None
+        }
+    }
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false
+    }
+}
+
+fn parse_operand(s: &str) -> Result<(ParameterMode, Expression), AsmErrorKind> {
+    let s = s.trim();
+    let bad_operand = || AsmErrorKind::BadOperand(s.to_string());
+
+    let (mode, expression) = if s.starts_with('[') && s.ends_with(']') {
+        (ParameterMode::Position, &s[1..s.len() - 1])
+    } else if let Some(value) = s.strip_prefix('#') {
+        (ParameterMode::Immediate, value)
+    } else if let Some(offset) = s.strip_prefix("rb") {
+        // `rb` alone is offset 0, otherwise the sign is part of the offset. Anything else, like
+        // `rbx`, isn't relative mode.
+        match offset.trim_start().chars().next() {
+            None => (ParameterMode::Relative, "0"),
+            Some('+') | Some('-') => (ParameterMode::Relative, offset),
+            Some(_) => return Err(bad_operand())
+        }
+    } else {
+        return Err(bad_operand());
+    };
+
+    let expression = Expression::parse(expression).ok_or_else(|| AsmErrorKind::BadExpression(expression.to_string()))?;
+    Ok((mode, expression))
+}
+
+enum Statement {
+    Instruction { line: usize, instruction: Instruction, operands: Vec<Expression> },
+    Data { line: usize, words: Vec<Expression> }
+}
+
+/// Assembles source text into a program image.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels: HashMap<String, i64> = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0i64;
+
+    // first pass: parse every line and find label addresses
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let error = |kind| AsmError { line, kind };
+
+        let mut text = text.split(';').next().unwrap().trim();
+
+        // `label:` or a listing's `12:` address prefix
+        while let Some(colon) = text.find(':') {
+            let prefix = text[..colon].trim();
+            if prefix.parse::<i64>().is_ok() {
+                // addresses from a disassembler listing are informational
+            } else if is_label(prefix) {
+                if labels.insert(prefix.to_string(), address).is_some() {
+                    return Err(error(AsmErrorKind::DuplicateLabel(prefix.to_string())));
+                }
+            } else {
+                break;
+            }
+            text = text[colon + 1..].trim();
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, rest) = match text.find(char::is_whitespace) {
+            Some(end) => (&text[..end], text[end..].trim()),
+            None => (text, "")
+        };
+        let operands: Vec<&str> = if rest.is_empty() { Vec::new() } else { rest.split(',').collect() };
+
+        if mnemonic.eq_ignore_ascii_case("db") || mnemonic.eq_ignore_ascii_case("data") {
+            let words = operands.iter()
+                .map(|w| Expression::parse(w).ok_or_else(|| error(AsmErrorKind::BadExpression(w.trim().to_string()))))
+                .collect::<Result<Vec<_>, _>>()?;
+            address += words.len() as i64;
+            statements.push(Statement::Data { line, words });
+            continue;
+        }
+
+        let op = Opcode::from_mnemonic(mnemonic).ok_or_else(|| error(AsmErrorKind::UnknownMnemonic(mnemonic.to_string())))?;
+        if operands.len() != op.parameter_count() {
+            return Err(error(AsmErrorKind::WrongOperandCount {
+                mnemonic: op.mnemonic().to_string(),
+                expected: op.parameter_count(),
+                found: operands.len()
+            }));
+        }
+
+        let mut modes = [ParameterMode::Position; 3];
+        let mut expressions = Vec::new();
+        for (i, operand) in operands.iter().enumerate() {
+            let (mode, expression) = parse_operand(operand).map_err(error)?;
+            modes[i] = mode;
+            expressions.push(expression);
+        }
+        if let Some(parameter) = op.write_parameter() {
+            if modes[parameter - 1] == ParameterMode::Immediate {
+                return Err(error(AsmErrorKind::ImmediateWrite(op.mnemonic().to_string())));
+            }
+        }
+
+        let instruction = Instruction { op, modes };
+        address += instruction.width();
+        statements.push(Statement::Instruction { line, instruction, operands: expressions });
+    }
+
+    // second pass: emit words now that every label is known
+    let mut program = Vec::new();
+    for statement in statements {
+        match statement {
+            Statement::Instruction { line, instruction, operands } => {
+                program.push(instruction.encode());
+                for operand in operands {
+                    program.push(operand.resolve(&labels).map_err(|kind| AsmError { line, kind })?);
+                }
+            },
+            Statement::Data { line, words } => {
+                for word in words {
+                    program.push(word.resolve(&labels).map_err(|kind| AsmError { line, kind })?);
+                }
+            }
+        }
+    }
+    Ok(program)
+}
+
+/// Formats a program image in the comma separated format of the days' `input.txt`.
+pub fn to_comma_separated(program: &[i64]) -> String {
+    program.iter().map(|word| word.to_string()).collect::<Vec<String>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{assemble, AsmError, AsmErrorKind};
+    use intcode::{Machine, StopReason};
+
+    #[test]
+    fn labels_and_modes() {
+        let source = "
+            ; count down from 3
+                    ADD #3, #0, [counter]
+            loop:   OUT [counter]
+                    ADD [counter], #-1, [counter]
+                    JNZ [counter], #loop
+                    HLT
+            counter: db 0
+        ";
+        let program = assemble(source).unwrap();
+        assert_eq!(program, vec![1101, 3, 0, 14, 4, 14, 1001, 14, -1, 14, 1005, 14, 4, 99, 0]);
+
+        let mut m = Machine::new(&program);
+        let mut outputs = Vec::new();
+        while let StopReason::Output(value) = m.run().unwrap() {
+            outputs.push(value);
+        }
+        assert_eq!(outputs, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn relative_store() {
+        // the regression test for relative-mode writes that used to need hand-encoding
+        let program = assemble("ARB #10\nIN rb+2\nOUT rb+2\nOUT [12]\nHLT").unwrap();
+        assert_eq!(program, vec![109, 10, 203, 2, 204, 2, 4, 12, 99]);
+
+        let mut m = Machine::new(&program);
+        m.push_input(42);
+        assert_eq!(m.run(), Ok(StopReason::Output(42)));
+        assert_eq!(m.run(), Ok(StopReason::Output(42)));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(assemble("NOP"), Err(AsmError { line: 1, kind: AsmErrorKind::UnknownMnemonic("NOP".to_string()) }));
+        assert_eq!(assemble("\nADD #1, #2, #3"), Err(AsmError { line: 2, kind: AsmErrorKind::ImmediateWrite("ADD".to_string()) }));
+        assert_eq!(assemble("JZ [0], #nowhere").unwrap_err().kind, AsmErrorKind::UndefinedLabel("nowhere".to_string()));
+        assert_eq!(assemble("OUT 5").unwrap_err().kind, AsmErrorKind::BadOperand("5".to_string()));
+        assert_eq!(assemble("a: HLT\na: HLT").unwrap_err().kind, AsmErrorKind::DuplicateLabel("a".to_string()));
+        assert_eq!(assemble("db 9223372036854775807 + 1").unwrap_err().kind, AsmErrorKind::BadExpression("9223372036854775807+1".to_string()));
+        assert_eq!(assemble("db 1 - 9223372036854775807 - 9223372036854775807").unwrap_err().kind, AsmErrorKind::BadExpression("1-9223372036854775807-9223372036854775807".to_string()));
+        assert_eq!(assemble("db -9223372036854775808, - 9223372036854775808"), Ok(vec![i64::MIN, i64::MIN]));
+        assert_eq!(assemble("OUT #-9223372036854775808"), Ok(vec![104, i64::MIN]));
+        assert_eq!(assemble("db 9223372036854775808").unwrap_err().kind, AsmErrorKind::BadExpression("9223372036854775808".to_string()));
+        assert_eq!(assemble("OUT rbx").unwrap_err().kind, AsmErrorKind::BadOperand("rbx".to_string()));
+    }
+
+    #[test]
+    fn disassembly_round_trips() {
+        let inputs = [
+            include_str!("../../day2/input.txt"),
+            include_str!("../../day5/input.txt"),
+            include_str!("../../day7/input.txt"),
+            include_str!("../../day9/input.txt"),
+            include_str!("../../day11/input.txt"),
+            include_str!("../../day13/input.txt"),
+            include_str!("../../day15/input.txt"),
+            "-9223372036854775808,104,-9223372036854775808,99"
+        ];
+        for input in inputs.iter() {
+            let program = intcode::parse_program(input);
+            let listing: Vec<String> = intcode::disassemble(&program).iter().map(|l| l.to_string()).collect();
+            assert_eq!(assemble(&listing.join("\n")).unwrap(), program);
+        }
+    }
+}
@@ -0,0 +1,29 @@
+// Assembles Intcode source into the comma separated format the days read,
+// e.g. `intcode-asm test.asm > input.txt`.
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::process;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: intcode-asm <source>");
+            process::exit(2);
+        }
+    };
+
+    let mut input = File::open(&path).expect("couldn't open input");
+    let mut s = String::new();
+    let _ = input.read_to_string(&mut s);
+
+    match intcode_asm::assemble(&s) {
+        Ok(program) => println!("{}", intcode_asm::to_comma_separated(&program)),
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
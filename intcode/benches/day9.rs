@@ -0,0 +1,159 @@
+// Compares the shared Machine against the interpreter loop day9 part2 used to carry around,
+// running the BOOST program in sensor mode. Run with `cargo bench -p intcode`.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use intcode::{Machine, StopReason};
+
+const PROGRAM: &str = include_str!("../../day9/input.txt");
+const ITERATIONS: u32 = 20;
+
+enum ParameterMode {
+    Position,
+    Immediate,
+    Relative
+}
+
+impl From<char> for ParameterMode {
+    fn from(num: char) -> ParameterMode {
+        match num {
+            '0' => ParameterMode::Position,
+            '1' => ParameterMode::Immediate,
+            '2' => ParameterMode::Relative,
+            _ => unreachable!()
+        }
+    }
+}
+
+fn get_value(h: &HashMap<i64, i64>, i: i64, p: &ParameterMode, relative_base: i64) -> i64 {
+    match p {
+        ParameterMode::Position => *h.get(&h[&i]).unwrap_or(&0),
+        ParameterMode::Immediate => *h.get(&i).unwrap_or(&0),
+        ParameterMode::Relative => *h.get(&(*h.get(&i).unwrap_or(&0) + relative_base)).unwrap_or(&0)
+    }
+}
+
+fn store_value(h: &mut HashMap<i64, i64>, i: i64, value: i64, p: &ParameterMode, relative_base: i64) {
+    match p {
+        ParameterMode::Position => {
+            h.insert(*h.get(&i).unwrap_or(&0), value);
+        },
+        ParameterMode::Relative => {
+            h.insert(*h.get(&i).unwrap_or(&0) + relative_base, value);
+        },
+        ParameterMode::Immediate => unreachable!()
+    }
+}
+
+// the day9 part2 loop before the intcode crate, with println swapped for collecting outputs
+fn run_legacy(h: &mut HashMap<i64, i64>, input: i64) -> Vec<i64> {
+    let mut outputs = Vec::new();
+    let mut current_op_index: i64 = 0;
+    let mut relative_base = 0;
+
+    while current_op_index < h.len() as i64 {
+        let op = h[&current_op_index];
+        let mut op_digits: Vec<char> = op.to_string().chars().collect();
+
+        while op_digits.len() < 5 {
+            op_digits.insert(0, '0');
+        }
+
+        let mut op_code = String::new();
+        op_code.push(op_digits[3]);
+        op_code.push(op_digits[4]);
+
+        let param_first = ParameterMode::from(op_digits[2]);
+        let param_second = ParameterMode::from(op_digits[1]);
+        let param_third = ParameterMode::from(op_digits[0]);
+
+        if op_code == "01" {
+            let first_value = get_value(h, current_op_index+1, &param_first, relative_base);
+            let second_value = get_value(h, current_op_index+2, &param_second, relative_base);
+            store_value(h, current_op_index+3, first_value+second_value, &param_third, relative_base);
+            current_op_index += 4;
+        } else if op_code == "02" {
+            let first_value = get_value(h, current_op_index+1, &param_first, relative_base);
+            let second_value = get_value(h, current_op_index+2, &param_second, relative_base);
+            store_value(h, current_op_index+3, first_value*second_value, &param_third, relative_base);
+            current_op_index += 4;
+        } else if op_code == "03" {
+            store_value(h, current_op_index+1, input, &param_first, relative_base);
+            current_op_index += 2;
+        } else if op_code == "04" {
+            outputs.push(get_value(h, current_op_index+1, &param_first, relative_base));
+            current_op_index += 2;
+        } else if op_code == "05" {
+            let first_value = get_value(h, current_op_index+1, &param_first, relative_base);
+            let second_value = get_value(h, current_op_index+2, &param_second, relative_base);
+            if first_value != 0 {
+                current_op_index = second_value;
+            } else {
+                current_op_index += 3;
+            }
+        } else if op_code == "06" {
+            let first_value = get_value(h, current_op_index+1, &param_first, relative_base);
+            let second_value = get_value(h, current_op_index+2, &param_second, relative_base);
+            if first_value == 0 {
+                current_op_index = second_value;
+            } else {
+                current_op_index += 3;
+            }
+        } else if op_code == "07" {
+            let first_value = get_value(h, current_op_index+1, &param_first, relative_base);
+            let second_value = get_value(h, current_op_index+2, &param_second, relative_base);
+            store_value(h, current_op_index+3, (first_value < second_value) as i64, &param_third, relative_base);
+            current_op_index += 4;
+        } else if op_code == "08" {
+            let first_value = get_value(h, current_op_index+1, &param_first, relative_base);
+            let second_value = get_value(h, current_op_index+2, &param_second, relative_base);
+            store_value(h, current_op_index+3, (first_value == second_value) as i64, &param_third, relative_base);
+            current_op_index += 4;
+        } else if op_code == "09" {
+            relative_base += get_value(h, current_op_index+1, &param_first, relative_base);
+            current_op_index += 2;
+        } else if op_code == "99" {
+            return outputs;
+        }
+    }
+    panic!("Didn't get halt opcode 99");
+}
+
+fn run_machine(program: &[i64], input: i64, decode_cache: bool) -> Vec<i64> {
+    let mut m = Machine::new(program);
+    m.set_decode_cache(decode_cache);
+    m.push_input(input);
+
+    let mut outputs = Vec::new();
+    while let StopReason::Output(value) = m.run().unwrap() {
+        outputs.push(value);
+    }
+    outputs
+}
+
+fn time<F: FnMut() -> Vec<i64>>(name: &str, mut f: F) -> Duration {
+    let expected = f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        assert_eq!(f(), expected);
+    }
+    let per_run = start.elapsed() / ITERATIONS;
+    println!("{:<24} {:>10.2?} per run", name, per_run);
+    per_run
+}
+
+fn main() {
+    let program = intcode::parse_program(PROGRAM);
+    let mut h = HashMap::new();
+    for (i, value) in program.iter().enumerate() {
+        h.insert(i as i64, *value);
+    }
+
+    let legacy = time("legacy day9 loop", || run_legacy(&mut h.clone(), 2));
+    let machine = time("Machine", || run_machine(&program, 2, false));
+    let cached = time("Machine + decode cache", || run_machine(&program, 2, true));
+
+    println!("speedup: {:.1}x, {:.1}x with decode cache",
+             legacy.as_secs_f64() / machine.as_secs_f64(),
+             legacy.as_secs_f64() / cached.as_secs_f64());
+}
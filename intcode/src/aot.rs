@@ -0,0 +1,182 @@
+//! Runtime support for Rust code generated by `intcode2rs`, see `translate`.
+//!
+//! Translated programs keep their memory and relative base in a `Cpu` and exchange values with
+//! the caller through an `Io`. Anything the translation can't handle ahead of time, like a jump
+//! to an address that isn't a known block or a write into the program's own code, is finished by
+//! the interpreter from the current state.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use crate::error::VmError;
+use crate::machine::{Machine, StopReason};
+use crate::memory::{Memory, MemoryError};
+use crate::snapshot::Snapshot;
+
+/// Where a translated program gets its inputs and puts its outputs.
+pub trait Io {
+    /// The next input, or `None` if there is none and the program should stop.
+    fn input(&mut self) -> Option<i64>;
+    fn output(&mut self, value: i64);
+}
+
+/// An `Io` reading from a fixed list of inputs and collecting the outputs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Buffered {
+    pub inputs: VecDeque<i64>,
+    pub outputs: Vec<i64>
+}
+
+impl Buffered {
+    pub fn new(inputs: &[i64]) -> Self {
+        Buffered { inputs: inputs.iter().copied().collect(), outputs: Vec::new() }
+    }
+}
+
+impl Io for Buffered {
+    fn input(&mut self) -> Option<i64> {
+        self.inputs.pop_front()
+    }
+
+    fn output(&mut self, value: i64) {
+        self.outputs.push(value);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AotError {
+    Vm(VmError),
+    /// The program needed input and `Io::input` had none.
+    InputExhausted { ip: i64 }
+}
+
+impl fmt::Display for AotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AotError::Vm(e) => write!(f, "{}", e),
+            AotError::InputExhausted { ip } => write!(f, "program at ip {} needs more input", ip)
+        }
+    }
+}
+
+impl Error for AotError {}
+
+impl From<VmError> for AotError {
+    fn from(e: VmError) -> Self {
+        AotError::Vm(e)
+    }
+}
+
+/// Memory and relative base of a translated program.
+#[derive(Debug, Clone)]
+pub struct Cpu {
+    pub memory: Memory,
+    pub rb: i64,
+    /// Which cells hold translated instructions.
+    code: Vec<bool>
+}
+
+impl Cpu {
+    /// `code` lists the `[start, end)` address ranges the translation compiled.
+    pub fn new(program: &[i64], code: &[(usize, usize)]) -> Self {
+        let mut code_cells = vec![false; program.len()];
+        for &(start, end) in code {
+            for cell in &mut code_cells[start..end] {
+                *cell = true;
+            }
+        }
+        Cpu { memory: Memory::new(program), rb: 0, code: code_cells }
+    }
+
+    fn address_error(&self, ip: i64, parameter: usize, e: MemoryError) -> AotError {
+        let opcode = self.memory.get(ip).unwrap_or(0);
+        AotError::Vm(match e {
+            MemoryError::NegativeAddress(address) => VmError::NegativeAddress { ip, opcode, parameter, address },
+            MemoryError::LimitExceeded(address) => VmError::MemoryLimitExceeded { ip, opcode, parameter, address }
+        })
+    }
+
+    fn overflow(&self, ip: i64) -> AotError {
+        AotError::Vm(VmError::Overflow { ip, opcode: self.memory.get(ip).unwrap_or(0) })
+    }
+
+    /// `a + b` for the instruction at `ip`, failing on overflow like the interpreter.
+    #[inline]
+    pub fn add(&self, ip: i64, a: i64, b: i64) -> Result<i64, AotError> {
+        a.checked_add(b).ok_or_else(|| self.overflow(ip))
+    }
+
+    #[inline]
+    pub fn mul(&self, ip: i64, a: i64, b: i64) -> Result<i64, AotError> {
+        a.checked_mul(b).ok_or_else(|| self.overflow(ip))
+    }
+
+    /// `offset` added to the relative base for the instruction at `ip`, failing on overflow like
+    /// the interpreter.
+    #[inline]
+    pub fn relative(&self, ip: i64, offset: i64) -> Result<i64, AotError> {
+        self.rb.checked_add(offset).ok_or_else(|| self.overflow(ip))
+    }
+
+    /// Adds `value` to the relative base for the instruction at `ip`.
+    #[inline]
+    pub fn adjust_rb(&mut self, ip: i64, value: i64) -> Result<(), AotError> {
+        self.rb = self.relative(ip, value)?;
+        Ok(())
+    }
+
+    /// Reads parameter `parameter` of the instruction at `ip` from `address`.
+    #[inline]
+    pub fn get(&self, ip: i64, parameter: usize, address: i64) -> Result<i64, AotError> {
+        self.memory.get(address).map_err(|e| self.address_error(ip, parameter, e))
+    }
+
+    #[inline]
+    pub fn set(&mut self, ip: i64, parameter: usize, address: i64, value: i64) -> Result<(), AotError> {
+        self.memory.set(address, value).map_err(|e| self.address_error(ip, parameter, e))
+    }
+
+    /// The word at `address`, for parameters the program patches at run time.
+    #[inline]
+    pub fn word(&self, address: i64) -> i64 {
+        self.memory.get(address).unwrap_or(0)
+    }
+
+    /// Like `set`, returns whether the write changed translated code.
+    #[inline]
+    pub fn set_checked(&mut self, ip: i64, parameter: usize, address: i64, value: i64) -> Result<bool, AotError> {
+        self.set(ip, parameter, address, value)?;
+        Ok(self.code.get(address as usize).copied().unwrap_or(false))
+    }
+
+    #[inline]
+    pub fn input(&mut self, ip: i64, io: &mut dyn Io) -> Result<i64, AotError> {
+        io.input().ok_or(AotError::InputExhausted { ip })
+    }
+
+    /// Runs the rest of the program in the interpreter, starting at `ip`.
+    pub fn interpret(&mut self, ip: i64, io: &mut dyn Io) -> Result<(), AotError> {
+        let mut m = Machine::from_snapshot(&Snapshot {
+            memory: self.memory.as_slice().to_vec(),
+            memory_limit: self.memory.limit(),
+            ip,
+            relative_base: self.rb,
+            inputs: Vec::new()
+        });
+        loop {
+            match m.run()? {
+                StopReason::Output(value) => io.output(value),
+                StopReason::NeedInput => match io.input() {
+                    Some(value) => m.push_input(value),
+                    None => return Err(AotError::InputExhausted { ip: m.ip() })
+                },
+                StopReason::Halted => return Ok(())
+            }
+        }
+    }
+}
+
+/// Runs `program` in the interpreter with the same interface as a translated program.
+pub fn interpret(program: &[i64], io: &mut dyn Io) -> Result<(), AotError> {
+    Cpu::new(program, &[]).interpret(0, io)
+}
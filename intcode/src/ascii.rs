@@ -0,0 +1,122 @@
+use crate::error::VmError;
+use crate::machine::{Machine, StopReason};
+
+/// Text I/O for programs that talk ASCII: they print prompts and camera views and read
+/// newline-terminated commands.
+///
+/// Outputs outside of the ASCII range aren't text, usually they're the program's final answer.
+/// They're collected separately and can be taken with `take_numbers`.
+#[derive(Debug, Clone)]
+pub struct AsciiIo {
+    machine: Machine,
+    numbers: Vec<i64>,
+    halted: bool
+}
+
+impl AsciiIo {
+    pub fn new(machine: Machine) -> Self {
+        AsciiIo { machine, numbers: Vec::new(), halted: false }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    pub fn into_inner(self) -> Machine {
+        self.machine
+    }
+
+    /// Whether the program reached opcode 99. Reads return no more text after that.
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Removes and returns the non-ASCII values output so far.
+    pub fn take_numbers(&mut self) -> Vec<i64> {
+        std::mem::take(&mut self.numbers)
+    }
+
+    /// Queues `line` followed by a newline as input.
+    pub fn write_line(&mut self, line: &str) {
+        for b in line.bytes() {
+            self.machine.push_input(b as i64);
+        }
+        self.machine.push_input(10);
+    }
+
+    /// Runs until the program prints a newline and returns the line without it.
+    ///
+    /// If the program needs input or halts first, whatever it printed of the line is returned,
+    /// or `None` if it printed nothing.
+    pub fn read_line(&mut self) -> Result<Option<String>, VmError> {
+        let mut line = self.read_text(true)?;
+        if line.is_empty() {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+
+    /// Runs until the program needs input or halts and returns all the text it printed, newlines
+    /// included.
+    pub fn read_until_prompt(&mut self) -> Result<String, VmError> {
+        self.read_text(false)
+    }
+
+    fn read_text(&mut self, until_newline: bool) -> Result<String, VmError> {
+        let mut text = String::new();
+        loop {
+            match self.machine.run()? {
+                StopReason::Output(value) if (0..=127).contains(&value) => {
+                    text.push(value as u8 as char);
+                    if until_newline && value == 10 {
+                        break;
+                    }
+                },
+                StopReason::Output(value) => self.numbers.push(value),
+                StopReason::NeedInput => break,
+                StopReason::Halted => {
+                    self.halted = true;
+                    break;
+                }
+            }
+        }
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ascii::AsciiIo;
+    use crate::{parse_program, Machine};
+
+    #[test]
+    fn prompts_and_numbers() {
+        // prints "ok\n?", a large number, "\n", then halts
+        let mut io = AsciiIo::new(Machine::new(&parse_program("104,111,104,107,104,10,104,63,104,500,104,10,99")));
+        assert_eq!(io.read_line(), Ok(Some("ok".to_string())));
+        assert_eq!(io.read_until_prompt(), Ok("?\n".to_string()));
+        assert!(io.halted());
+        assert_eq!(io.take_numbers(), vec![500]);
+        assert_eq!(io.read_line(), Ok(None));
+    }
+
+    #[test]
+    fn echo_lines() {
+        // echoes its input forever
+        let mut io = AsciiIo::new(Machine::new(&parse_program("3,100,4,100,1105,1,0")));
+        assert_eq!(io.read_line(), Ok(None));
+
+        io.write_line("hello");
+        io.write_line("world");
+        assert_eq!(io.read_line(), Ok(Some("hello".to_string())));
+        assert_eq!(io.read_until_prompt(), Ok("world\n".to_string()));
+        assert!(!io.halted());
+    }
+}
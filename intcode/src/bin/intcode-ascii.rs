@@ -0,0 +1,56 @@
+// Runs an ASCII Intcode program interactively, e.g. `intcode-ascii day25/input.txt`.
+// Lines typed on stdin are sent to the program, non-ASCII outputs are printed as `[value]`.
+// With `-s <file>` the lines of the file are sent first, before switching to stdin.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, Read};
+use std::process;
+use intcode::{AsciiIo, Machine};
+
+fn usage() -> ! {
+    eprintln!("usage: intcode-ascii <program> [-s <script>]");
+    process::exit(2);
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args.next().unwrap_or_else(|| usage());
+    let mut script = Vec::new();
+    match (args.next(), args.next()) {
+        (None, _) => {},
+        (Some(ref flag), Some(ref script_path)) if flag == "-s" => {
+            let s = std::fs::read_to_string(script_path).expect("couldn't read script");
+            script = s.lines().map(|l| l.to_string()).collect();
+        },
+        _ => usage()
+    }
+
+    let mut input = File::open(&path).expect("couldn't open input");
+    let mut s = String::new();
+    let _ = input.read_to_string(&mut s);
+    let mut io = AsciiIo::new(Machine::new(&intcode::parse_program(&s)));
+
+    let stdin = io::stdin();
+    let mut lines = script.into_iter().map(Ok).chain(stdin.lock().lines());
+    loop {
+        match io.read_until_prompt() {
+            Ok(text) => print!("{}", text),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        for value in io.take_numbers() {
+            println!("[{}]", value);
+        }
+        if io.halted() {
+            return;
+        }
+
+        match lines.next() {
+            Some(line) => io.write_line(&line.expect("couldn't read stdin")),
+            None => return
+        }
+    }
+}
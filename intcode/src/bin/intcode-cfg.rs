@@ -0,0 +1,36 @@
+// Prints the control flow graph of an Intcode program image in Graphviz format, e.g.
+// `intcode-cfg day13/input.txt | dot -Tsvg > day13.svg`. With `--summary` it lists the functions
+// instead.
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::process;
+use intcode::Cfg;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let summary = args.iter().any(|a| a == "--summary");
+    let path = match args.iter().find(|a| !a.starts_with("--")) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: intcode-cfg <program> [--summary]");
+            process::exit(2);
+        }
+    };
+
+    let mut input = File::open(path).expect("couldn't open input");
+    let mut s = String::new();
+    let _ = input.read_to_string(&mut s);
+    let cfg = Cfg::new(&intcode::parse_program(&s));
+
+    if !summary {
+        print!("{}", cfg.to_dot());
+        return;
+    }
+    println!("{} blocks, {} functions", cfg.blocks.len(), cfg.functions.len());
+    for function in &cfg.functions {
+        let frame = function.frame_size.map(|size| format!(", frame {}", size)).unwrap_or_default();
+        println!("fn {}: {} blocks{}", function.entry, function.blocks.len(), frame);
+    }
+}
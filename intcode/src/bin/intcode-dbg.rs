@@ -0,0 +1,58 @@
+// Interactive debugger for Intcode programs, e.g. `intcode-dbg day13/input.txt`.
+// Type `help` for the commands. An empty line repeats the last command.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::process;
+use intcode::debugger::{Command, Debugger};
+use intcode::Machine;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: intcode-dbg <program>");
+            process::exit(2);
+        }
+    };
+
+    let mut input = File::open(&path).expect("couldn't open input");
+    let mut s = String::new();
+    let _ = input.read_to_string(&mut s);
+    let program = intcode::parse_program(&s);
+
+    let mut debugger = Debugger::new(Machine::new(&program));
+    println!("loaded {} words from {}", program.len(), path);
+    println!("=> {}", debugger.current_instruction());
+
+    let stdin = io::stdin();
+    let mut last_command: Option<Command> = None;
+    loop {
+        print!("(dbg) ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+
+        let command = if line.trim().is_empty() {
+            match &last_command {
+                Some(command) => Ok(command.clone()),
+                None => continue
+            }
+        } else {
+            Command::parse(&line)
+        };
+
+        match command {
+            Ok(Command::Quit) => break,
+            Ok(command) => {
+                print!("{}", debugger.execute(&command));
+                last_command = Some(command);
+            },
+            Err(e) => println!("{}", e)
+        }
+    }
+}
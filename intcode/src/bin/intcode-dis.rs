@@ -0,0 +1,25 @@
+// Prints a listing of an Intcode program image, e.g. `intcode-dis day9/input.txt`.
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::process;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: intcode-dis <program>");
+            process::exit(2);
+        }
+    };
+
+    let mut input = File::open(&path).expect("couldn't open input");
+    let mut s = String::new();
+    let _ = input.read_to_string(&mut s);
+    let program = intcode::parse_program(&s);
+
+    for line in intcode::disassemble(&program) {
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,32 @@
+// Replays recorded sessions, e.g. `intcode-replay day13.session`, and fails if any of them plays
+// out differently than it was recorded. The day 13 and 15 solutions write sessions with
+// `--record <file>`.
+
+use std::env;
+use std::process;
+use intcode::Session;
+
+fn main() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("usage: intcode-replay <session>...");
+        process::exit(2);
+    }
+
+    let mut failed = false;
+    for path in &paths {
+        let result = Session::load(path)
+            .map_err(|e| e.to_string())
+            .and_then(|session| session.replay().map(|()| session.events.len()).map_err(|e| e.to_string()));
+        match result {
+            Ok(events) => println!("{}: ok, {} events", path, events),
+            Err(e) => {
+                println!("{}: {}", path, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
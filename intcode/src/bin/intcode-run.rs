@@ -0,0 +1,143 @@
+// Runs an Intcode program and prints its outputs, e.g. `intcode-run day9/input.txt 2`.
+//
+// Words are 64 bit and overflowing one is an error. `--word bigint` runs with arbitrary precision
+// instead, for programs that need larger values. `--isa day2|day5` rejects instructions newer than
+// that day's, `--check` only prints which instruction set the program needs.
+//
+// `--max-steps`, `--max-memory` (in cells) and `--timeout` (in milliseconds) stop programs that
+// run away.
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+use intcode::{BigInt, IsaProfile, Machine, StopReason, VmError, Word, DEFAULT_MEMORY_LIMIT};
+
+const USAGE: &str = "usage: intcode-run <program> [--word i64|bigint] [--isa day2|day5|day9] [--check] [--max-steps n] [--max-memory cells] [--timeout ms] [input...] [-f <file>]";
+
+struct Settings {
+    profile: IsaProfile,
+    max_steps: Option<u64>,
+    max_memory: usize,
+    timeout: Option<Duration>
+}
+
+fn run<W: Word>(program: &[i64], settings: &Settings, inputs: &[i64]) -> Result<(), VmError> {
+    let mut m = Machine::<W>::load(program);
+    m.set_profile(settings.profile);
+    m.set_step_limit(settings.max_steps);
+    m.set_memory_limit(settings.max_memory);
+    m.set_time_limit(settings.timeout);
+    for value in inputs {
+        m.push_input(W::from_i64(*value));
+    }
+    loop {
+        match m.run()? {
+            StopReason::Output(value) => println!("{}", value),
+            StopReason::Halted => return Ok(()),
+            StopReason::NeedInput => {
+                eprintln!("program at ip {} asked for more than the {} input(s) given", m.ip(), inputs.len());
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// Removes `--name <value>` from `args`, returning the value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    if i + 1 >= args.len() {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let value = args.remove(i + 1);
+    args.remove(i);
+    Some(value)
+}
+
+/// Parses the value of `--name <value>` if it was given.
+fn parse_option<T: FromStr>(args: &mut Vec<String>, name: &str) -> Option<T> {
+    let value = take_option(args, name)?;
+    match value.parse() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            eprintln!("bad value {} for {}", value, name);
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let word = take_option(&mut args, "--word").unwrap_or_else(|| "i64".to_string());
+    let profile = match take_option(&mut args, "--isa").map(|isa| isa.parse()) {
+        None => IsaProfile::default(),
+        Some(Ok(profile)) => profile,
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let settings = Settings {
+        profile,
+        max_steps: parse_option(&mut args, "--max-steps"),
+        max_memory: parse_option(&mut args, "--max-memory").unwrap_or(DEFAULT_MEMORY_LIMIT),
+        timeout: parse_option(&mut args, "--timeout").map(Duration::from_millis)
+    };
+    let check = match args.iter().position(|a| a == "--check") {
+        Some(i) => {
+            args.remove(i);
+            true
+        },
+        None => false
+    };
+    if args.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let path = args.remove(0);
+
+    let inputs = match intcode::inputs_from_args(args) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let mut input = File::open(&path).expect("couldn't open input");
+    let mut s = String::new();
+    let _ = input.read_to_string(&mut s);
+    let program = intcode::parse_program(&s);
+
+    if check {
+        let compatibility = intcode::check_compatibility(&program);
+        match compatibility.needed_at {
+            Some(address) => println!("needs {}, first at {}", compatibility.profile, address),
+            None => println!("needs {}", compatibility.profile)
+        }
+        for address in compatibility.invalid {
+            println!("no instruction at {} yet, it has to be written at run time", address);
+        }
+        return;
+    }
+
+    let result = match word.as_str() {
+        "i64" => run::<i64>(&program, &settings, &inputs),
+        "bigint" => run::<BigInt>(&program, &settings, &inputs),
+        _ => {
+            eprintln!("unknown word type {}", word);
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
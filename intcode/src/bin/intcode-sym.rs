@@ -0,0 +1,86 @@
+// Runs an Intcode program symbolically and prints every path it can take with the conditions on
+// its inputs, e.g. `intcode-sym day5/input.txt --max-inputs 1`. Inputs, and cells given with
+// `--cell`, are in the inclusive range of `--bounds` (default the i32 range). With `--target` it
+// prints inputs that make the program output that value instead.
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::ops::RangeInclusive;
+use std::process;
+use intcode::symbolic::Executor;
+
+fn usage() -> ! {
+    eprintln!("usage: intcode-sym <program> [--max-inputs n] [--bounds lo..hi] [--cell address]... [--target value]");
+    process::exit(2);
+}
+
+fn parse_bounds(s: &str) -> Option<RangeInclusive<i64>> {
+    let mut parts = s.splitn(2, "..");
+    let low = parts.next()?.parse().ok()?;
+    let high = parts.next()?.parse().ok()?;
+    Some(low..=high)
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut path = None;
+    let mut max_inputs = None;
+    let mut bounds = None;
+    let mut cells = Vec::new();
+    let mut target = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--max-inputs" => max_inputs = Some(args.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| usage())),
+            "--bounds" => bounds = Some(args.next().and_then(|b| parse_bounds(&b)).unwrap_or_else(|| usage())),
+            "--cell" => cells.push(args.next().and_then(|a| a.parse::<i64>().ok()).unwrap_or_else(|| usage())),
+            "--target" => target = Some(args.next().and_then(|t| t.parse::<i64>().ok()).unwrap_or_else(|| usage())),
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => usage()
+        }
+    }
+    let path = path.unwrap_or_else(|| usage());
+
+    let mut input = File::open(path).expect("couldn't open input");
+    let mut s = String::new();
+    let _ = input.read_to_string(&mut s);
+    let mut executor = Executor::new(&intcode::parse_program(&s));
+    let bounds = bounds.unwrap_or(i32::MIN as i64..=i32::MAX as i64);
+    executor.set_input_bounds(bounds.clone());
+    for address in cells {
+        executor.add_symbolic_cell(address, bounds.clone());
+    }
+    if let Some(max_inputs) = max_inputs {
+        executor.set_max_inputs(max_inputs);
+    }
+
+    if let Some(target) = target {
+        match executor.find_output(target) {
+            Some(witness) => {
+                println!("inputs: {:?}", witness.inputs);
+                for (address, value) in &witness.cells {
+                    println!("[{}] = {}", address, value);
+                }
+            },
+            None => {
+                eprintln!("no inputs found that output {}", target);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    for (n, path) in executor.explore().iter().enumerate() {
+        println!("path {}: {}", n, path.end);
+        let mut outputs = path.outputs.iter().peekable();
+        for (i, constraint) in path.constraints.iter().enumerate() {
+            while let Some(output) = outputs.next_if(|o| o.constraints <= i) {
+                println!("    output {}", output.value);
+            }
+            println!("    {}", constraint);
+        }
+        for output in outputs {
+            println!("    output {}", output.value);
+        }
+    }
+}
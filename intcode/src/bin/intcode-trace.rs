@@ -0,0 +1,133 @@
+// Runs an Intcode program with tracing, e.g.
+// `intcode-trace day9/input.txt --input 2 --summary --jsonl day9.jsonl`.
+//
+// Outputs are printed as the program produces them. The program stops when it halts or when it
+// needs more input than `--input` gave it. If it fails, the traces are still written and the exit
+// status is 1. `--coverage <file>` writes an annotated listing of what ran, with a summary at the
+// end.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::process;
+use intcode::coverage::Coverage;
+use intcode::trace::{BinaryTracer, JsonlTracer, Profile};
+use intcode::{Machine, StopReason, TraceStep, Tracer};
+
+const USAGE: &str = "usage: intcode-trace <program> [--input a,b,...] [--jsonl <file>] [--binary <file>] [--coverage <file>] [--summary]";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+/// The tracers the options asked for.
+struct Tracers<W: Write> {
+    jsonl: Option<JsonlTracer<W>>,
+    binary: Option<BinaryTracer<W>>,
+    profile: Option<Profile>,
+    coverage: Option<(String, Coverage)>
+}
+
+impl<W: Write> Tracer for Tracers<W> {
+    fn trace(&mut self, step: &TraceStep) {
+        if let Some(t) = self.jsonl.as_mut() {
+            t.trace(step);
+        }
+        if let Some(t) = self.binary.as_mut() {
+            t.trace(step);
+        }
+        if let Some(t) = self.profile.as_mut() {
+            t.trace(step);
+        }
+        if let Some((_, t)) = self.coverage.as_mut() {
+            t.trace(step);
+        }
+    }
+
+    fn stopped(&mut self, ip: i64) {
+        if let Some((_, t)) = self.coverage.as_mut() {
+            t.stopped(ip);
+        }
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args.next().unwrap_or_else(|| usage());
+
+    let mut inputs = Vec::new();
+    let mut jsonl = None;
+    let mut binary = None;
+    let mut profile = None;
+    let mut coverage = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                let values = args.next().unwrap_or_else(|| usage());
+                for value in values.split(',') {
+                    inputs.push(value.trim().parse::<i64>().unwrap_or_else(|_| {
+                        eprintln!("`{}` isn't a number", value);
+                        process::exit(2);
+                    }));
+                }
+            },
+            "--jsonl" => {
+                let file = File::create(args.next().unwrap_or_else(|| usage())).expect("couldn't create trace file");
+                jsonl = Some(JsonlTracer::new(BufWriter::new(file)));
+            },
+            "--binary" => {
+                let file = File::create(args.next().unwrap_or_else(|| usage())).expect("couldn't create trace file");
+                binary = Some(BinaryTracer::new(BufWriter::new(file)));
+            },
+            "--coverage" => coverage = Some((args.next().unwrap_or_else(|| usage()), Coverage::new())),
+            "--summary" => profile = Some(Profile::new()),
+            _ => usage()
+        }
+    }
+
+    let mut input = File::open(&path).expect("couldn't open input");
+    let mut s = String::new();
+    let _ = input.read_to_string(&mut s);
+    let program = intcode::parse_program(&s);
+
+    let mut m = Machine::new(&program);
+    for value in inputs {
+        m.push_input(value);
+    }
+
+    let mut tracers = Tracers { jsonl, binary, profile, coverage };
+    let mut failed = false;
+    loop {
+        match m.run_traced(&mut tracers) {
+            Ok(StopReason::Output(value)) => println!("{}", value),
+            Ok(StopReason::NeedInput) => {
+                eprintln!("program needs more input");
+                break;
+            },
+            Ok(StopReason::Halted) => break,
+            Err(e) => {
+                eprintln!("{}", e);
+                failed = true;
+                break;
+            }
+        }
+    }
+
+    if let Some(t) = tracers.jsonl {
+        t.finish().expect("couldn't write trace");
+    }
+    if let Some(t) = tracers.binary {
+        t.finish().expect("couldn't write trace");
+    }
+    if let Some((path, c)) = tracers.coverage {
+        let report = c.annotate(&program) + "\n" + &c.summary(&program);
+        fs::write(path, report).expect("couldn't write coverage");
+    }
+    if let Some(p) = tracers.profile {
+        eprint!("{}", p.summary(10));
+    }
+    if failed {
+        process::exit(1);
+    }
+}
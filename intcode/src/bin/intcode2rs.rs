@@ -0,0 +1,27 @@
+// Translates an Intcode program image to Rust source, e.g.
+// `intcode2rs day9/input.txt boost > boost.rs`. The generated function needs the intcode crate,
+// see `intcode::aot`.
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::process;
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = match args.next() {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: intcode2rs <program> [function name]");
+            process::exit(2);
+        }
+    };
+    let name = args.next().unwrap_or_else(|| "run".to_string());
+
+    let mut input = File::open(&path).expect("couldn't open input");
+    let mut s = String::new();
+    let _ = input.read_to_string(&mut s);
+    let program = intcode::parse_program(&s);
+
+    print!("{}", intcode::translate(&program, &name));
+}
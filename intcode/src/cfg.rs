@@ -0,0 +1,360 @@
+//! Control flow graphs of program images.
+//!
+//! Blocks are found by following jumps from address 0. Jumps with an immediate target inside the
+//! image are resolved, any other target is only known at run time and the jump is marked
+//! indirect.
+//!
+//! Code that keeps a stack on the relative base, like days 9, 13 and 15, calls a function by
+//! storing the return address at `rb+0` and jumping to it. The function moves the relative base
+//! past its frame with `ARB`, moves it back and returns by jumping to `rb+0`. Those jumps become
+//! `Exit::Call` and `Exit::Return`, and every call target starts a `Function`.
+//!
+//! Only the image as loaded is analysed, code the program writes at run time isn't seen.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use crate::decode::Opcode;
+use crate::disasm::{disassemble_at, Item, Line, Operand};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Direct(usize),
+    /// Read from memory, or an immediate outside of the image.
+    Indirect(Operand)
+}
+
+/// How control leaves a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// Runs into the block at the address, which some jump also leads to.
+    FallThrough(usize),
+    Jump(Target),
+    Branch { taken: Target, not_taken: usize },
+    /// Jumps after storing `returns_to` at `rb+0`.
+    Call { target: Target, returns_to: usize },
+    /// Jumps to the address at `rb+0`.
+    Return,
+    Halt,
+    /// Runs into a word that isn't an instruction, at the address.
+    Invalid(usize)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    pub lines: Vec<Line>,
+    pub exit: Exit
+}
+
+impl Block {
+    /// Blocks control can continue at within the function, so not call targets or returns.
+    pub fn successors(&self) -> Vec<usize> {
+        match self.exit {
+            Exit::FallThrough(next) => vec![next],
+            Exit::Jump(Target::Direct(target)) => vec![target],
+            Exit::Branch { taken: Target::Direct(target), not_taken } => vec![target, not_taken],
+            Exit::Branch { not_taken, .. } => vec![not_taken],
+            Exit::Call { returns_to, .. } => vec![returns_to],
+            _ => Vec::new()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub entry: usize,
+    /// The `ARB` the function starts with, if it does.
+    pub frame_size: Option<i64>,
+    /// Start addresses of the function's blocks, in order.
+    pub blocks: Vec<usize>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    pub blocks: BTreeMap<usize, Block>,
+    /// Address 0 and every call target, by entry address.
+    pub functions: Vec<Function>
+}
+
+fn instruction(line: &Line) -> Option<(Opcode, &[Operand])> {
+    match &line.item {
+        Item::Instruction { op, operands } => Some((*op, operands)),
+        Item::Data(_) => None
+    }
+}
+
+/// The target of a jump and whether it is always taken, `None` for other instructions and jumps
+/// that are never taken.
+fn jump(op: Opcode, operands: &[Operand], program: &[i64]) -> Option<(Target, bool)> {
+    if op != Opcode::JumpIfTrue && op != Opcode::JumpIfFalse {
+        return None;
+    }
+    let always = match (op, operands[0]) {
+        (Opcode::JumpIfTrue, Operand::Immediate(0)) => return None,
+        (Opcode::JumpIfFalse, Operand::Immediate(condition)) if condition != 0 => return None,
+        (_, Operand::Immediate(_)) => true,
+        _ => false
+    };
+    let target = match operands[1] {
+        Operand::Immediate(target) if target >= 0 && (target as usize) < program.len() => Target::Direct(target as usize),
+        operand => Target::Indirect(operand)
+    };
+    Some((target, always))
+}
+
+/// The value an `ADD` or `MUL` of two immediates stores at `rb+0`, `None` if it overflows, which
+/// the interpreter fails on.
+fn return_address(line: &Line) -> Option<i64> {
+    match instruction(line)? {
+        (Opcode::Add, [Operand::Immediate(a), Operand::Immediate(b), Operand::Relative(0)]) => a.checked_add(*b),
+        (Opcode::Multiply, [Operand::Immediate(a), Operand::Immediate(b), Operand::Relative(0)]) => a.checked_mul(*b),
+        _ => None
+    }
+}
+
+/// The exit of a block ending with the jump in `lines`' last line.
+fn jump_exit(lines: &[Line], target: Target, always: bool, next: usize) -> Exit {
+    if !always {
+        return Exit::Branch { taken: target, not_taken: next };
+    }
+    if target == Target::Indirect(Operand::Relative(0)) {
+        return Exit::Return;
+    }
+    let stored = lines.len().checked_sub(2).and_then(|i| return_address(&lines[i]));
+    if stored == Some(next as i64) {
+        Exit::Call { target, returns_to: next }
+    } else {
+        Exit::Jump(target)
+    }
+}
+
+/// Addresses that start a block.
+fn find_leaders(program: &[i64]) -> BTreeSet<usize> {
+    let mut leaders = BTreeSet::new();
+    let mut visited = BTreeSet::new();
+    let mut worklist = vec![0];
+    leaders.insert(0);
+
+    while let Some(start) = worklist.pop() {
+        let mut lines: Vec<Line> = Vec::new();
+        let mut address = start;
+        while address < program.len() && visited.insert(address) {
+            let line = disassemble_at(program, address);
+            let next = address + line.item.width();
+            let (op, operands) = match instruction(&line) {
+                Some(instruction) => instruction,
+                None => break
+            };
+            let jumped = jump(op, operands, program);
+            lines.push(line);
+
+            if op == Opcode::Halt {
+                break;
+            }
+            if let Some((target, always)) = jumped {
+                let mut follow = |address: usize| if leaders.insert(address) {
+                    worklist.push(address);
+                };
+                if let Target::Direct(target) = target {
+                    follow(target);
+                }
+                match jump_exit(&lines, target, always, next) {
+                    Exit::Branch { .. } | Exit::Call { .. } => follow(next),
+                    _ => {}
+                }
+                break;
+            }
+            address = next;
+        }
+    }
+    leaders
+}
+
+fn build_block(program: &[i64], start: usize, leaders: &BTreeSet<usize>) -> Block {
+    let mut lines = Vec::new();
+    let mut address = start;
+    let exit = loop {
+        if address >= program.len() {
+            break Exit::Invalid(address);
+        }
+        let line = disassemble_at(program, address);
+        let next = address + line.item.width();
+        let (op, operands) = match instruction(&line) {
+            Some(instruction) => instruction,
+            None => break Exit::Invalid(address)
+        };
+        let jumped = jump(op, operands, program);
+        lines.push(line);
+
+        if op == Opcode::Halt {
+            break Exit::Halt;
+        }
+        if let Some((target, always)) = jumped {
+            break jump_exit(&lines, target, always, next);
+        }
+        if leaders.contains(&next) {
+            break Exit::FallThrough(next);
+        }
+        address = next;
+    };
+    Block { start, lines, exit }
+}
+
+impl Cfg {
+    pub fn new(program: &[i64]) -> Self {
+        let leaders = find_leaders(program);
+        let blocks: BTreeMap<usize, Block> = leaders.iter()
+            .map(|start| (*start, build_block(program, *start, &leaders)))
+            .collect();
+
+        let mut entries = BTreeSet::new();
+        entries.insert(0);
+        for block in blocks.values() {
+            if let Exit::Call { target: Target::Direct(target), .. } = block.exit {
+                entries.insert(target);
+            }
+        }
+
+        // a block belongs to the first function that reaches it, jumps to another function's
+        // entry are tail calls
+        let mut owner = HashMap::new();
+        let mut functions = Vec::new();
+        for entry in &entries {
+            let mut function_blocks = Vec::new();
+            let mut worklist = vec![*entry];
+            while let Some(start) = worklist.pop() {
+                if owner.contains_key(&start) || (start != *entry && entries.contains(&start)) {
+                    continue;
+                }
+                owner.insert(start, *entry);
+                function_blocks.push(start);
+                worklist.extend(blocks[&start].successors());
+            }
+            function_blocks.sort_unstable();
+
+            let frame_size = match blocks[entry].lines.first().and_then(instruction) {
+                Some((Opcode::AdjustRelativeBase, [Operand::Immediate(size)])) if *size > 0 => Some(*size),
+                _ => None
+            };
+            functions.push(Function { entry: *entry, frame_size, blocks: function_blocks });
+        }
+
+        Cfg { blocks, functions }
+    }
+
+    pub fn block(&self, start: usize) -> Option<&Block> {
+        self.blocks.get(&start)
+    }
+
+    /// The function the block starting at `block` belongs to.
+    pub fn function_of(&self, block: usize) -> Option<&Function> {
+        self.functions.iter().find(|f| f.blocks.binary_search(&block).is_ok())
+    }
+
+    /// The graph in Graphviz format, one cluster per function.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        out.push_str("digraph cfg {\n");
+        out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        for function in &self.functions {
+            let _ = writeln!(out, "    subgraph cluster_{} {{", function.entry);
+            let label = match function.frame_size {
+                Some(size) => format!("fn {}, frame {}", function.entry, size),
+                None => format!("fn {}", function.entry)
+            };
+            let _ = writeln!(out, "        label=\"{}\";", label);
+            for start in &function.blocks {
+                let _ = writeln!(out, "        {}", self.dot_node(&self.blocks[start]));
+            }
+            out.push_str("    }\n");
+        }
+
+        for block in self.blocks.values() {
+            let from = block.start;
+            let mut edge = |target: Target, attributes: &str| match target {
+                Target::Direct(to) => {
+                    let _ = writeln!(out, "    b{} -> b{}{};", from, to, attributes);
+                },
+                Target::Indirect(operand) => {
+                    let _ = writeln!(out, "    i{} [shape=diamond, label=\"{}\"];", from, operand);
+                    let _ = writeln!(out, "    b{} -> i{}{};", from, from, attributes);
+                }
+            };
+            match block.exit {
+                Exit::FallThrough(next) => edge(Target::Direct(next), ""),
+                Exit::Jump(target) => edge(target, ""),
+                Exit::Branch { taken, not_taken } => {
+                    edge(taken, " [label=\"taken\"]");
+                    edge(Target::Direct(not_taken), " [label=\"not taken\"]");
+                },
+                Exit::Call { target, returns_to } => {
+                    edge(target, " [label=\"call\", style=dashed]");
+                    edge(Target::Direct(returns_to), " [style=dotted]");
+                },
+                Exit::Return | Exit::Halt | Exit::Invalid(_) => {}
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn dot_node(&self, block: &Block) -> String {
+        let mut label = String::new();
+        for line in &block.lines {
+            let _ = write!(label, "{}\\l", line.to_string().trim_start());
+        }
+        match block.exit {
+            Exit::Return => label.push_str("return\\l"),
+            Exit::Invalid(address) => { let _ = write!(label, "{}: not an instruction\\l", address); },
+            _ => {}
+        }
+        format!("b{} [label=\"{}\"];", block.start, label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cfg::{Cfg, Exit, Function, Target};
+    use crate::disasm::Operand;
+    use crate::parse_program;
+
+    #[test]
+    fn branches_and_indirect_jumps() {
+        // 0: IN [12]   2: JNZ [12], #7   5: OUT #0   7: JZ [12], [12]   10: HLT
+        let cfg = Cfg::new(&parse_program("3,12,1005,12,7,104,0,6,12,12,99,0,0"));
+        let exits: Vec<(usize, Exit)> = cfg.blocks.values().map(|b| (b.start, b.exit)).collect();
+        assert_eq!(exits, vec![
+            (0, Exit::Branch { taken: Target::Direct(7), not_taken: 5 }),
+            (5, Exit::FallThrough(7)),
+            (7, Exit::Branch { taken: Target::Indirect(Operand::Position(12)), not_taken: 10 }),
+            (10, Exit::Halt)
+        ]);
+        assert_eq!(cfg.block(0).unwrap().lines.len(), 2);
+    }
+
+    #[test]
+    fn calls_and_returns() {
+        // 0: ARB #100   2: ADD #0, #9, rb+0   6: JNZ #1, #11   9: HLT
+        // 11: ARB #2   13: OUT #5   15: ARB #-2   17: JZ #0, rb+0
+        let cfg = Cfg::new(&parse_program("109,100,21101,0,9,0,1105,1,11,99,0,109,2,104,5,109,-2,2106,0,0"));
+        assert_eq!(cfg.block(0).unwrap().exit, Exit::Call { target: Target::Direct(11), returns_to: 9 });
+        assert_eq!(cfg.block(11).unwrap().exit, Exit::Return);
+        assert_eq!(cfg.functions, vec![
+            Function { entry: 0, frame_size: Some(100), blocks: vec![0, 9] },
+            Function { entry: 11, frame_size: Some(2), blocks: vec![11] }
+        ]);
+
+        let dot = cfg.to_dot();
+        assert!(dot.contains("subgraph cluster_11 {\n        label=\"fn 11, frame 2\";"));
+        assert!(dot.contains("b0 -> b11 [label=\"call\", style=dashed];"));
+        assert!(dot.contains("b0 -> b9 [style=dotted];"));
+        assert!(dot.contains("b11 [label=\"11: ARB #2\\l13: OUT #5\\l15: ARB #-2\\l17: JZ #0, rb+0\\lreturn\\l\"];"));
+    }
+
+    #[test]
+    fn overflowing_return_address() {
+        let cfg = Cfg::new(&[21101, i64::MAX, 1, 0, 1105, 1, 7, 99]);
+        assert_eq!(cfg.block(0).unwrap().exit, Exit::Jump(Target::Direct(7)));
+    }
+}
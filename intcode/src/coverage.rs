@@ -0,0 +1,272 @@
+//! Which instructions of a program a run executed, see `Coverage`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use crate::decode::{Instruction, Opcode, ParameterMode};
+use crate::disasm::{disassemble_at, Item, Line, Operand};
+use crate::trace::{TraceStep, Tracer};
+
+/// How often a jump at one address went each way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Branch {
+    pub taken: u64,
+    pub not_taken: u64
+}
+
+/// An executed address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit {
+    pub count: u64,
+    /// The instruction last executed there, which isn't the one in the image if the program
+    /// wrote over it.
+    pub instruction: Instruction,
+    pub operands: [i64; 3]
+}
+
+impl Hit {
+    fn item(&self) -> Item {
+        let operands = self.operands.iter().zip(&self.instruction.modes)
+            .take(self.instruction.op.parameter_count())
+            .map(|(value, mode)| Operand::new(*mode, *value))
+            .collect();
+        Item::Instruction { op: self.instruction.op, operands }
+    }
+}
+
+/// Executed addresses, branch directions and opcode/mode combinations of a traced run.
+///
+/// Collect it by passing it to `Machine::run_traced`, one `Coverage` can cover several runs. A
+/// jump counts as taken when the next instruction executed isn't the one after it, so a jump to
+/// the next instruction counts as not taken.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    pub hits: BTreeMap<i64, Hit>,
+    /// `JNZ` and `JZ` instructions by address.
+    pub branches: BTreeMap<i64, Branch>,
+    /// Executions per opcode word, with mode digits of parameters the opcode doesn't have left
+    /// out.
+    pub combinations: BTreeMap<i64, u64>,
+    /// Address of the jump the last step executed, its direction is known at the next step or
+    /// when the run stops.
+    pending_branch: Option<i64>
+}
+
+fn mode_name(mode: ParameterMode) -> &'static str {
+    match mode {
+        ParameterMode::Position => "pos",
+        ParameterMode::Immediate => "imm",
+        ParameterMode::Relative => "rel"
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Coverage::default()
+    }
+
+    /// Credits the pending jump with the direction it went, now that the next ip is known.
+    fn resolve_branch(&mut self, ip: i64) {
+        if let Some(jump) = self.pending_branch.take() {
+            let branch = self.branches.entry(jump).or_default();
+            if ip == jump + 3 {
+                branch.not_taken += 1;
+            } else {
+                branch.taken += 1;
+            }
+        }
+    }
+
+    /// Lines of the listing `annotate` prints, with the hit of each executed one. Past the end
+    /// of the image only executed addresses are listed.
+    fn lines(&self, program: &[i64]) -> Vec<(Line, Option<&Hit>)> {
+        let mut lines = Vec::new();
+        let mut address = 0;
+        while address < program.len() {
+            let (line, hit) = match self.hits.get(&(address as i64)) {
+                Some(hit) => (Line { address, item: hit.item() }, Some(hit)),
+                None => {
+                    let mut line = disassemble_at(program, address);
+                    let next_hit = self.hits.range(address as i64 + 1..).next().map(|(a, _)| *a as usize);
+                    if next_hit.is_some_and(|next| next < address + line.item.width()) {
+                        line.item = Item::Data(program.get(address).copied().unwrap_or(0));
+                    }
+                    (line, None)
+                }
+            };
+            address += line.item.width();
+            lines.push((line, hit));
+        }
+        for (hit_address, hit) in self.hits.range(address as i64..) {
+            if (*hit_address as usize) < address {
+                continue;
+            }
+            let line = Line { address: *hit_address as usize, item: hit.item() };
+            address = line.address + line.item.width();
+            lines.push((line, Some(hit)));
+        }
+        lines
+    }
+
+    /// A listing of `program` with how often each instruction was executed, `-` for never, and
+    /// which ways each branch went.
+    ///
+    /// Executed addresses are listed as executed, with a note where that differs from the image.
+    /// Unexecuted code is disassembled from the image, stopping short of executed addresses.
+    pub fn annotate(&self, program: &[i64]) -> String {
+        let mut s = String::new();
+        for (line, hit) in self.lines(program) {
+            let mut notes = Vec::new();
+            let count = match (hit, &line.item) {
+                (Some(hit), _) => {
+                    if disassemble_at(program, line.address) != line {
+                        notes.push("written at run time".to_string());
+                    }
+                    hit.count.to_string()
+                },
+                (None, Item::Instruction { .. }) => "-".to_string(),
+                (None, Item::Data(_)) => String::new()
+            };
+            if let Some(branch) = self.branches.get(&(line.address as i64)) {
+                notes.push(format!("taken {}, not taken {}", branch.taken, branch.not_taken));
+            }
+            let _ = write!(s, "{:>10} {}", count, line);
+            if !notes.is_empty() {
+                let _ = write!(s, "  ; {}", notes.join(", "));
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    /// How many instructions of the `annotate` listing were executed, the branches that only
+    /// went one way and the opcode/mode combinations seen.
+    pub fn summary(&self, program: &[i64]) -> String {
+        let mut s = String::new();
+        let lines = self.lines(program);
+        let never = lines.iter().filter(|(line, hit)| hit.is_none() && matches!(line.item, Item::Instruction { .. })).count();
+        let total = self.hits.len() + never;
+        let percent = if total == 0 { 0.0 } else { 100.0 * self.hits.len() as f64 / total as f64 };
+        let _ = writeln!(s, "{} of {} instructions executed ({:.1}%)", self.hits.len(), total, percent);
+
+        let both = self.branches.values().filter(|b| b.taken > 0 && b.not_taken > 0).count();
+        let _ = writeln!(s, "{} of {} branches went both ways", both, self.branches.len());
+        for (address, branch) in &self.branches {
+            if branch.taken == 0 {
+                let _ = writeln!(s, "  {:>5} never taken", address);
+            } else if branch.not_taken == 0 {
+                let _ = writeln!(s, "  {:>5} always taken", address);
+            }
+        }
+
+        let _ = writeln!(s, "opcode/mode combinations");
+        for (word, count) in &self.combinations {
+            let op = Opcode::from_code(word % 100).unwrap();
+            let modes: Vec<&str> = (0..op.parameter_count())
+                .map(|i| mode_name(ParameterMode::from_digit(word / 10i64.pow(i as u32 + 2) % 10).unwrap()))
+                .collect();
+            let _ = writeln!(s, "  {:>5} {:<4}{:<12}{:>12}", word, op.mnemonic(), modes.join(","), count);
+        }
+        s
+    }
+}
+
+impl Tracer for Coverage {
+    fn trace(&mut self, step: &TraceStep) {
+        self.resolve_branch(step.ip);
+
+        let hit = self.hits.entry(step.ip).or_insert(Hit { count: 0, instruction: step.instruction, operands: step.operands });
+        hit.count += 1;
+        hit.instruction = step.instruction;
+        hit.operands = step.operands;
+
+        let mut instruction = step.instruction;
+        for mode in instruction.modes.iter_mut().skip(instruction.op.parameter_count()) {
+            *mode = ParameterMode::Position;
+        }
+        *self.combinations.entry(instruction.encode()).or_insert(0) += 1;
+
+        if let Opcode::JumpIfTrue | Opcode::JumpIfFalse = step.instruction.op {
+            self.pending_branch = Some(step.ip);
+        }
+    }
+
+    fn stopped(&mut self, ip: i64) {
+        self.resolve_branch(ip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coverage::{Branch, Coverage};
+    use crate::{parse_program, Machine, StopReason};
+
+    #[test]
+    fn day5_comparison() {
+        // outputs 1 if the input is 8, 0 otherwise
+        let program = parse_program("3,12,1008,12,8,12,1005,12,11,104,0,99,0");
+        let mut coverage = Coverage::new();
+        for input in &[8, 7, 6] {
+            let mut m = Machine::new(&program);
+            m.push_input(*input);
+            while let StopReason::Output(_) = m.run_traced(&mut coverage).unwrap() {}
+        }
+
+        assert_eq!(coverage.hits[&0].count, 3);
+        assert!(!coverage.hits.contains_key(&12));
+        assert_eq!(coverage.branches[&6], Branch { taken: 1, not_taken: 2 });
+        assert_eq!(coverage.annotate(&program), [
+            "         3     0: IN [12]",
+            "         3     2: EQ [12], #8, [12]",
+            "         3     6: JNZ [12], #11  ; taken 1, not taken 2",
+            "         2     9: OUT #0",
+            "         3    11: HLT",
+            "              12: DATA 0",
+            ""
+        ].join("\n"));
+
+        let summary = coverage.summary(&program);
+        assert!(summary.starts_with("5 of 5 instructions executed (100.0%)\n1 of 1 branches went both ways\n"));
+        assert!(summary.contains("   104 OUT imm                    2\n"));
+        assert!(summary.contains("  1008 EQ  pos,imm,pos            3\n"));
+    }
+
+    #[test]
+    fn unexecuted_and_modified_code() {
+        // writes OUT #7 over the data at 8 and runs it, never running the ADD at 11
+        let program = parse_program("1101,104,0,8,1105,1,8,0,0,7,99,1,0,0,0");
+        let mut coverage = Coverage::new();
+        let mut m = Machine::new(&program);
+        assert_eq!(m.run_traced(&mut coverage), Ok(StopReason::Output(7)));
+        assert_eq!(m.run_traced(&mut coverage), Ok(StopReason::Halted));
+
+        let annotated = coverage.annotate(&program);
+        assert!(annotated.contains("         1     4: JNZ #1, #8  ; taken 1, not taken 0\n               7: DATA 0\n"), "{}", annotated);
+        assert!(annotated.contains("         1     8: OUT #7  ; written at run time\n"), "{}", annotated);
+        assert!(annotated.contains("         -    11: ADD [0], [0], [0]\n"), "{}", annotated);
+        assert!(coverage.summary(&program).starts_with("4 of 5 instructions executed (80.0%)\n"));
+    }
+
+    #[test]
+    fn runs_ending_after_a_jump() {
+        // the jump isn't taken and the machine fails on the next word, the next run mustn't count
+        // its first instruction as the jump's destination
+        let mut coverage = Coverage::new();
+        assert!(Machine::new(&parse_program("1105,0,0,42")).run_traced(&mut coverage).is_err());
+        assert_eq!(Machine::new(&parse_program("99")).run_traced(&mut coverage), Ok(StopReason::Halted));
+        assert_eq!(coverage.branches[&0], Branch { taken: 0, not_taken: 1 });
+    }
+
+    #[test]
+    fn code_past_the_image() {
+        // writes HLT far past the end of the image and jumps there
+        let program = parse_program("1101,0,99,100000,1105,1,100000");
+        let mut coverage = Coverage::new();
+        assert_eq!(Machine::new(&program).run_traced(&mut coverage), Ok(StopReason::Halted));
+        assert_eq!(coverage.annotate(&program), [
+            "         1     0: ADD #0, #99, [100000]",
+            "         1     4: JNZ #1, #100000  ; taken 1, not taken 0",
+            "         1 100000: HLT  ; written at run time",
+            ""
+        ].join("\n"));
+    }
+}
@@ -0,0 +1,425 @@
+//! Command interpreter behind `intcode-dbg`.
+//!
+//! A `Debugger` owns a `Machine` and runs it one instruction at a time so it can stop on
+//! breakpoints and on writes to watched cells. Outputs are printed as they happen instead of
+//! stopping execution.
+//!
+//! The machine keeps an undo log of the last `DEFAULT_HISTORY` instructions, so the debugger can
+//! also run backwards to a breakpoint or a watch and tell which instruction last wrote a cell.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use crate::disasm::disassemble_at;
+use crate::machine::{Machine, StopReason};
+
+/// Instructions the debugger keeps in the undo log unless told otherwise with `history`.
+pub const DEFAULT_HISTORY: usize = 100_000;
+
+pub const HELP: &str = "\
+step [n]         execute n instructions (default 1)
+continue         run until a breakpoint, a watch, input is needed or the program halts
+step-back [n]    undo n instructions (default 1)
+reverse-continue run backwards until a breakpoint, a watch or the start of the history
+last-write <addr>
+                 print when and where the cell at addr was last written
+history [n|off]  keep the last n instructions for stepping back, or none
+break [addr]     set a breakpoint at addr, or list breakpoints
+delete <addr>    remove the breakpoint at addr
+watch [addr]     stop when the cell at addr changes, or list watches
+unwatch <addr>   remove the watch on addr
+mem <from> <len> print len cells starting at from
+rb               print the relative base
+ip               print the instruction pointer and the instruction there
+input <n>...     queue values for opcode 3
+help             print this message
+quit             exit";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Step(usize),
+    Continue,
+    StepBack(usize),
+    ReverseContinue,
+    LastWrite(i64),
+    /// `None` shows the history, `Some(0)` turns it off.
+    History(Option<usize>),
+    Break(Option<i64>),
+    Delete(i64),
+    Watch(Option<i64>),
+    Unwatch(i64),
+    Mem { from: i64, len: usize },
+    RelativeBase,
+    Ip,
+    Input(Vec<i64>),
+    Help,
+    Quit
+}
+
+impl Command {
+    /// Parses one line of debugger input. Commands can be shortened to their first letter where
+    /// that's unambiguous (`s`, `c`, `b`, `d`, `w`, `m`, `i`, `h`, `q`), the reverse commands to
+    /// their initials (`sb`, `rc`, `lw`).
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or_else(|| "empty command".to_string())?;
+        let arguments: Vec<&str> = words.collect();
+
+        let number = |i: usize| -> Result<i64, String> {
+            let argument = arguments.get(i).ok_or_else(|| format!("{} needs an argument, see `help`", name))?;
+            argument.parse().map_err(|_| format!("`{}` isn't a number", argument))
+        };
+        let optional_number = |i: usize| -> Result<Option<i64>, String> {
+            if arguments.len() > i { number(i).map(Some) } else { Ok(None) }
+        };
+        let count = |i: usize| -> Result<usize, String> {
+            match number(i)? {
+                n if n < 0 => Err(format!("`{}` can't be negative", n)),
+                n => Ok(n as usize)
+            }
+        };
+
+        match name {
+            "step" | "s" => Ok(Command::Step(if arguments.is_empty() { 1 } else { count(0)? })),
+            "continue" | "c" => Ok(Command::Continue),
+            "step-back" | "sb" => Ok(Command::StepBack(if arguments.is_empty() { 1 } else { count(0)? })),
+            "reverse-continue" | "rc" => Ok(Command::ReverseContinue),
+            "last-write" | "lw" => Ok(Command::LastWrite(number(0)?)),
+            "history" => match arguments.first() {
+                None => Ok(Command::History(None)),
+                Some(&"off") => Ok(Command::History(Some(0))),
+                Some(_) => Ok(Command::History(Some(count(0)?)))
+            },
+            "break" | "b" => Ok(Command::Break(optional_number(0)?)),
+            "delete" | "d" => Ok(Command::Delete(number(0)?)),
+            "watch" | "w" => Ok(Command::Watch(optional_number(0)?)),
+            "unwatch" => Ok(Command::Unwatch(number(0)?)),
+            "mem" | "m" => Ok(Command::Mem { from: number(0)?, len: count(1)? }),
+            "rb" => Ok(Command::RelativeBase),
+            "ip" => Ok(Command::Ip),
+            "input" | "i" => {
+                let values = (0..arguments.len()).map(number).collect::<Result<Vec<_>, _>>()?;
+                if values.is_empty() {
+                    return Err("input needs at least one value".to_string());
+                }
+                Ok(Command::Input(values))
+            },
+            "help" | "h" => Ok(Command::Help),
+            "quit" | "q" => Ok(Command::Quit),
+            _ => Err(format!("unknown command `{}`, see `help`", name))
+        }
+    }
+}
+
+pub struct Debugger {
+    machine: Machine,
+    breakpoints: BTreeSet<i64>,
+    /// Watched addresses and the value they had when last checked.
+    watches: BTreeMap<i64, i64>
+}
+
+impl Debugger {
+    /// Takes over `machine` with an empty undo log of `DEFAULT_HISTORY` instructions, so it can't
+    /// be stepped back to before now.
+    pub fn new(mut machine: Machine) -> Self {
+        machine.set_undo_log(Some(DEFAULT_HISTORY));
+        Debugger {
+            machine,
+            breakpoints: BTreeSet::new(),
+            watches: BTreeMap::new()
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// The instruction at the instruction pointer, formatted like an `intcode-dis` line.
+    pub fn current_instruction(&self) -> String {
+        if self.machine.ip() < 0 {
+            return format!("{:>5}: <out of memory>", self.machine.ip());
+        }
+        disassemble_at(self.machine.memory().as_slice(), self.machine.ip() as usize).to_string()
+    }
+
+    /// Runs a command and returns the text to show for it. `Quit` is left to the caller.
+    pub fn execute(&mut self, command: &Command) -> String {
+        let mut out = String::new();
+        match command {
+            Command::Step(n) => self.advance(Some(*n), &mut out),
+            Command::Continue => self.advance(None, &mut out),
+            Command::StepBack(n) => self.retreat(Some(*n), &mut out),
+            Command::ReverseContinue => self.retreat(None, &mut out),
+            Command::LastWrite(address) => match self.machine.last_write(*address) {
+                Some(write) => {
+                    let _ = writeln!(out, "[{}]: {} -> {} at step {} by ip {}", address, write.old, write.new, write.steps, write.ip);
+                },
+                None => {
+                    let _ = writeln!(out, "no write to [{}] in the history", address);
+                }
+            },
+            Command::History(depth) => {
+                match depth {
+                    Some(0) => self.machine.set_undo_log(None),
+                    Some(depth) => self.machine.set_undo_log(Some(*depth)),
+                    None => {}
+                }
+                match self.machine.undo_depth() {
+                    Some(depth) => {
+                        let _ = writeln!(out, "keeping the last {} instructions, {} so far", depth, self.machine.undoable_steps());
+                    },
+                    None => out.push_str("history off\n")
+                }
+            },
+            Command::Break(Some(address)) => {
+                self.breakpoints.insert(*address);
+                let _ = writeln!(out, "breakpoint at {}", address);
+            },
+            Command::Break(None) => {
+                if self.breakpoints.is_empty() {
+                    out.push_str("no breakpoints\n");
+                }
+                for address in &self.breakpoints {
+                    let _ = writeln!(out, "breakpoint at {}", address);
+                }
+            },
+            Command::Delete(address) => {
+                if !self.breakpoints.remove(address) {
+                    let _ = writeln!(out, "no breakpoint at {}", address);
+                }
+            },
+            Command::Watch(Some(address)) => {
+                if *address < 0 {
+                    let _ = writeln!(out, "can't watch negative address {}", address);
+                } else {
+                    let value = self.machine.read(*address);
+                    self.watches.insert(*address, value);
+                    let _ = writeln!(out, "watching [{}] = {}", address, value);
+                }
+            },
+            Command::Watch(None) => {
+                if self.watches.is_empty() {
+                    out.push_str("no watches\n");
+                }
+                for (address, value) in &self.watches {
+                    let _ = writeln!(out, "watching [{}] = {}", address, value);
+                }
+            },
+            Command::Unwatch(address) => {
+                if self.watches.remove(address).is_none() {
+                    let _ = writeln!(out, "no watch on {}", address);
+                }
+            },
+            Command::Mem { from, len } => self.dump_memory(*from, *len, &mut out),
+            Command::RelativeBase => {
+                let _ = writeln!(out, "rb = {}", self.machine.relative_base());
+            },
+            Command::Ip => {
+                let _ = writeln!(out, "=> {}", self.current_instruction());
+            },
+            Command::Input(values) => {
+                for value in values {
+                    self.machine.push_input(*value);
+                }
+            },
+            Command::Help => {
+                out.push_str(HELP);
+                out.push('\n');
+            },
+            Command::Quit => {}
+        }
+        out
+    }
+
+    /// Executes instructions until `limit` of them ran (or forever if `None`) or something
+    /// worth stopping for happens. The first instruction always runs, so continuing from a
+    /// breakpoint doesn't stop on it again.
+    fn advance(&mut self, limit: Option<usize>, out: &mut String) {
+        let mut executed = 0;
+        loop {
+            if limit == Some(executed) {
+                break;
+            }
+            if executed > 0 && self.breakpoints.contains(&self.machine.ip()) {
+                let _ = writeln!(out, "breakpoint at {}", self.machine.ip());
+                break;
+            }
+
+            match self.machine.step() {
+                Ok(None) => {},
+                Ok(Some(StopReason::Output(value))) => {
+                    let _ = writeln!(out, "output: {}", value);
+                },
+                Ok(Some(StopReason::NeedInput)) => {
+                    out.push_str("waiting for input\n");
+                    break;
+                },
+                Ok(Some(StopReason::Halted)) => {
+                    out.push_str("halted\n");
+                    break;
+                },
+                Err(e) => {
+                    let _ = writeln!(out, "error: {}", e);
+                    break;
+                }
+            }
+            executed += 1;
+
+            if self.check_watches(out) {
+                break;
+            }
+        }
+        let _ = writeln!(out, "=> {}", self.current_instruction());
+    }
+
+    /// Undoes instructions until `limit` of them were undone (or all of them if `None`), the ip
+    /// lands on a breakpoint or a watched cell changes back.
+    fn retreat(&mut self, limit: Option<usize>, out: &mut String) {
+        if self.machine.undo_depth().is_none() {
+            out.push_str("history off, see `help`\n");
+            return;
+        }
+        let mut undone = 0;
+        while limit != Some(undone) {
+            if !self.machine.step_back() {
+                out.push_str("start of history\n");
+                break;
+            }
+            undone += 1;
+
+            let watched = self.check_watches(out);
+            if self.breakpoints.contains(&self.machine.ip()) {
+                let _ = writeln!(out, "breakpoint at {}", self.machine.ip());
+                break;
+            }
+            if watched {
+                break;
+            }
+        }
+        let _ = writeln!(out, "=> {}", self.current_instruction());
+    }
+
+    /// Reports every watched cell whose value changed and returns whether any did.
+    fn check_watches(&mut self, out: &mut String) -> bool {
+        let mut changed = false;
+        for (address, value) in self.watches.iter_mut() {
+            let new_value = self.machine.read(*address);
+            if new_value != *value {
+                let _ = writeln!(out, "watch [{}]: {} -> {}", address, value, new_value);
+                *value = new_value;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn dump_memory(&self, from: i64, len: usize, out: &mut String) {
+        if from < 0 {
+            let _ = writeln!(out, "can't read negative address {}", from);
+            return;
+        }
+        // nothing past the memory limit can ever be written, so there's no point reading it
+        let len = len.min(self.machine.memory().limit());
+        let end = match from.checked_add(len as i64) {
+            Some(end) => end,
+            None => {
+                let _ = writeln!(out, "{} cells from {} run past the last address", len, from);
+                return;
+            }
+        };
+        let addresses: Vec<i64> = (from..end).collect();
+        for row in addresses.chunks(8) {
+            let _ = write!(out, "{:>5}:", row[0]);
+            for address in row {
+                let _ = write!(out, " {}", self.machine.read(*address));
+            }
+            out.push('\n');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debugger::{Command, Debugger};
+    use crate::{parse_program, Machine};
+
+    fn debugger(program: &str) -> Debugger {
+        Debugger::new(Machine::new(&parse_program(program)))
+    }
+
+    fn run(d: &mut Debugger, line: &str) -> String {
+        d.execute(&Command::parse(line).unwrap())
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(Command::parse("s"), Ok(Command::Step(1)));
+        assert_eq!(Command::parse("step 10"), Ok(Command::Step(10)));
+        assert_eq!(Command::parse("mem 4 2"), Ok(Command::Mem { from: 4, len: 2 }));
+        assert_eq!(Command::parse("input 1 -2"), Ok(Command::Input(vec![1, -2])));
+        assert_eq!(Command::parse("b"), Ok(Command::Break(None)));
+        assert!(Command::parse("mem 4").is_err());
+        assert!(Command::parse("step x").is_err());
+        assert!(Command::parse("frobnicate").is_err());
+    }
+
+    #[test]
+    fn step_and_breakpoints() {
+        // day5's "is the input 8" example followed by a countdown to exercise breakpoints
+        let mut d = debugger("3,9,8,9,10,9,4,9,99,-1,8");
+        assert_eq!(run(&mut d, "ip"), "=>     0: IN [9]\n");
+        assert_eq!(run(&mut d, "step"), "waiting for input\n=>     0: IN [9]\n");
+
+        run(&mut d, "input 8");
+        run(&mut d, "break 6");
+        assert_eq!(run(&mut d, "c"), "breakpoint at 6\n=>     6: OUT [9]\n");
+        assert_eq!(run(&mut d, "c"), "output: 1\nhalted\n=>     8: HLT\n");
+    }
+
+    #[test]
+    fn watches_and_memory() {
+        let mut d = debugger("1101,2,3,9,1001,9,10,9,99,0");
+        assert_eq!(run(&mut d, "watch 9"), "watching [9] = 0\n");
+        assert_eq!(run(&mut d, "c"), "watch [9]: 0 -> 5\n=>     4: ADD [9], #10, [9]\n");
+        assert_eq!(run(&mut d, "c"), "watch [9]: 5 -> 15\n=>     8: HLT\n");
+        assert_eq!(run(&mut d, "mem 6 5"), "    6: 10 9 99 15 0\n");
+        assert_eq!(run(&mut d, "rb"), "rb = 0\n");
+        assert_eq!(run(&mut d, "mem 9223372036854775800 10"), "10 cells from 9223372036854775800 run past the last address\n");
+        d.machine.set_memory_limit(12);
+        assert_eq!(run(&mut d, "mem 8 9223372036854775807"), "    8: 99 15 0 0 0 0 0 0\n   16: 0 0 0 0\n");
+    }
+
+    #[test]
+    fn reverse_execution() {
+        let mut d = debugger("1101,2,3,9,1001,9,10,9,99,0");
+        assert_eq!(run(&mut d, "sb"), "start of history\n=>     0: ADD #2, #3, [9]\n");
+        assert_eq!(run(&mut d, "c"), "halted\n=>     8: HLT\n");
+        assert_eq!(run(&mut d, "lw 9"), "[9]: 5 -> 15 at step 2 by ip 4\n");
+        assert_eq!(run(&mut d, "lw 0"), "no write to [0] in the history\n");
+
+        run(&mut d, "break 4");
+        assert_eq!(run(&mut d, "rc"), "breakpoint at 4\n=>     4: ADD [9], #10, [9]\n");
+        assert_eq!(run(&mut d, "mem 9 1"), "    9: 5\n");
+        assert_eq!(run(&mut d, "watch 9"), "watching [9] = 5\n");
+        assert_eq!(run(&mut d, "reverse-continue"), "watch [9]: 5 -> 0\n=>     0: ADD #2, #3, [9]\n");
+        assert_eq!(run(&mut d, "step-back 3"), "start of history\n=>     0: ADD #2, #3, [9]\n");
+        assert_eq!(run(&mut d, "s 2"), "watch [9]: 0 -> 5\n=>     4: ADD [9], #10, [9]\n");
+    }
+
+    #[test]
+    fn history() {
+        assert_eq!(Command::parse("history 5"), Ok(Command::History(Some(5))));
+        assert_eq!(Command::parse("history off"), Ok(Command::History(Some(0))));
+
+        let mut d = debugger("1105,1,0");
+        assert_eq!(run(&mut d, "history"), "keeping the last 100000 instructions, 0 so far\n");
+        run(&mut d, "step 10");
+        assert_eq!(run(&mut d, "history 4"), "keeping the last 4 instructions, 4 so far\n");
+        assert_eq!(run(&mut d, "sb 5"), "start of history\n=>     0: JNZ #1, #0\n");
+        assert_eq!(d.machine().steps(), 6);
+
+        assert_eq!(run(&mut d, "history off"), "history off\n");
+        run(&mut d, "step 10");
+        assert_eq!(run(&mut d, "sb"), "history off, see `help`\n");
+        assert_eq!(run(&mut d, "lw 0"), "no write to [0] in the history\n");
+    }
+}
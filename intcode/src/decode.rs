@@ -0,0 +1,199 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParameterMode {
+    Position,
+    Immediate,
+    Relative
+}
+
+impl ParameterMode {
+    pub fn from_digit(digit: i64) -> Option<ParameterMode> {
+        match digit {
+            0 => Some(ParameterMode::Position),
+            1 => Some(ParameterMode::Immediate),
+            2 => Some(ParameterMode::Relative),
+            _ => None
+        }
+    }
+
+    pub fn digit(self) -> i64 {
+        match self {
+            ParameterMode::Position => 0,
+            ParameterMode::Immediate => 1,
+            ParameterMode::Relative => 2
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Add,
+    Multiply,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Halt
+}
+
+impl Opcode {
+    pub const ALL: [Opcode; 10] = [
+        Opcode::Add,
+        Opcode::Multiply,
+        Opcode::Input,
+        Opcode::Output,
+        Opcode::JumpIfTrue,
+        Opcode::JumpIfFalse,
+        Opcode::LessThan,
+        Opcode::Equals,
+        Opcode::AdjustRelativeBase,
+        Opcode::Halt
+    ];
+
+    pub fn from_code(code: i64) -> Option<Opcode> {
+        match code {
+            1 => Some(Opcode::Add),
+            2 => Some(Opcode::Multiply),
+            3 => Some(Opcode::Input),
+            4 => Some(Opcode::Output),
+            5 => Some(Opcode::JumpIfTrue),
+            6 => Some(Opcode::JumpIfFalse),
+            7 => Some(Opcode::LessThan),
+            8 => Some(Opcode::Equals),
+            9 => Some(Opcode::AdjustRelativeBase),
+            99 => Some(Opcode::Halt),
+            _ => None
+        }
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Multiply => 2,
+            Opcode::Input => 3,
+            Opcode::Output => 4,
+            Opcode::JumpIfTrue => 5,
+            Opcode::JumpIfFalse => 6,
+            Opcode::LessThan => 7,
+            Opcode::Equals => 8,
+            Opcode::AdjustRelativeBase => 9,
+            Opcode::Halt => 99
+        }
+    }
+
+    pub fn parameter_count(self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 3,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 2,
+            Opcode::Input | Opcode::Output | Opcode::AdjustRelativeBase => 1,
+            Opcode::Halt => 0
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "ADD",
+            Opcode::Multiply => "MUL",
+            Opcode::Input => "IN",
+            Opcode::Output => "OUT",
+            Opcode::JumpIfTrue => "JNZ",
+            Opcode::JumpIfFalse => "JZ",
+            Opcode::LessThan => "LT",
+            Opcode::Equals => "EQ",
+            Opcode::AdjustRelativeBase => "ARB",
+            Opcode::Halt => "HLT"
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+        Opcode::ALL.iter().copied().find(|op| op.mnemonic().eq_ignore_ascii_case(mnemonic))
+    }
+
+    /// 1-based index of the parameter this opcode writes to, if any.
+    pub fn write_parameter(self) -> Option<usize> {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => Some(3),
+            Opcode::Input => Some(1),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Instruction {
+    pub op: Opcode,
+    pub modes: [ParameterMode; 3]
+}
+
+impl Instruction {
+    /// Number of words the instruction occupies, opcode included.
+    pub fn width(&self) -> i64 {
+        self.op.parameter_count() as i64 + 1
+    }
+
+    /// Encodes the instruction back into an opcode word.
+    pub fn encode(&self) -> i64 {
+        self.op.code()
+            + 100 * self.modes[0].digit()
+            + 1000 * self.modes[1].digit()
+            + 10000 * self.modes[2].digit()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    UnknownOpcode,
+    /// 1-based index of the parameter with an invalid mode digit.
+    InvalidParameterMode(usize)
+}
+
+/// Splits an opcode word into its opcode and the modes of its three parameters.
+pub fn decode(word: i64) -> Result<Instruction, DecodeError> {
+    if !(0..100_000).contains(&word) {
+        return Err(DecodeError::UnknownOpcode);
+    }
+
+    let op = Opcode::from_code(word % 100).ok_or(DecodeError::UnknownOpcode)?;
+
+    let mut modes = [ParameterMode::Position; 3];
+    let mut mode_digits = word / 100;
+    for (i, mode) in modes.iter_mut().enumerate() {
+        *mode = ParameterMode::from_digit(mode_digits % 10).ok_or(DecodeError::InvalidParameterMode(i + 1))?;
+        mode_digits /= 10;
+    }
+
+    Ok(Instruction { op, modes })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode::{decode, DecodeError, Instruction, Opcode, ParameterMode};
+
+    #[test]
+    fn decodes_modes() {
+        let i = decode(21002).unwrap();
+        assert_eq!(i, Instruction { op: Opcode::Multiply, modes: [ParameterMode::Position, ParameterMode::Immediate, ParameterMode::Relative] });
+        assert_eq!(i.encode(), 21002);
+        assert_eq!(decode(99).unwrap().op, Opcode::Halt);
+    }
+
+    #[test]
+    fn rejects_bad_words() {
+        assert_eq!(decode(42), Err(DecodeError::UnknownOpcode));
+        assert_eq!(decode(-1), Err(DecodeError::UnknownOpcode));
+        assert_eq!(decode(100_001), Err(DecodeError::UnknownOpcode));
+        assert_eq!(decode(301), Err(DecodeError::InvalidParameterMode(1)));
+        assert_eq!(decode(90001), Err(DecodeError::InvalidParameterMode(3)));
+    }
+
+    #[test]
+    fn round_trips_every_valid_word() {
+        for word in 0..100_000 {
+            if let Ok(i) = decode(word) {
+                assert_eq!(i.encode(), word);
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+use crate::decode::{decode, Opcode, ParameterMode};
+
+/// An instruction parameter as written in a listing: `[addr]`, `#imm` or `rb+off`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Position(i64),
+    Immediate(i64),
+    Relative(i64)
+}
+
+impl Operand {
+    pub fn new(mode: ParameterMode, value: i64) -> Self {
+        match mode {
+            ParameterMode::Position => Operand::Position(value),
+            ParameterMode::Immediate => Operand::Immediate(value),
+            ParameterMode::Relative => Operand::Relative(value)
+        }
+    }
+
+    pub fn mode(self) -> ParameterMode {
+        match self {
+            Operand::Position(_) => ParameterMode::Position,
+            Operand::Immediate(_) => ParameterMode::Immediate,
+            Operand::Relative(_) => ParameterMode::Relative
+        }
+    }
+
+    pub fn value(self) -> i64 {
+        match self {
+            Operand::Position(value) | Operand::Immediate(value) | Operand::Relative(value) => value
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Operand::Position(address) => write!(f, "[{}]", address),
+            Operand::Immediate(value) => write!(f, "#{}", value),
+            Operand::Relative(offset) if offset < 0 => write!(f, "rb{}", offset),
+            Operand::Relative(offset) => write!(f, "rb+{}", offset)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Instruction { op: Opcode, operands: Vec<Operand> },
+    Data(i64)
+}
+
+impl Item {
+    /// Number of words the item occupies in the image.
+    pub fn width(&self) -> usize {
+        match self {
+            Item::Instruction { operands, .. } => operands.len() + 1,
+            Item::Data(_) => 1
+        }
+    }
+}
+
+/// One line of a listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub address: usize,
+    pub item: Item
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>5}: ", self.address)?;
+        match &self.item {
+            Item::Instruction { op, operands } => {
+                write!(f, "{}", op.mnemonic())?;
+                for (i, operand) in operands.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(f, "{}{}", separator, operand)?;
+                }
+                Ok(())
+            },
+            Item::Data(value) => write!(f, "DATA {}", value)
+        }
+    }
+}
+
+/// Decodes the item starting at `address`.
+///
+/// Words that aren't a valid opcode, that set modes for parameters the opcode doesn't take, whose
+/// parameters would run past the end of the image or that would write to an immediate operand
+/// come back as `Item::Data`.
+pub fn disassemble_at(program: &[i64], address: usize) -> Line {
+    let word = program.get(address).copied().unwrap_or(0);
+    let data = Line { address, item: Item::Data(word) };
+
+    let instruction = match decode(word) {
+        Ok(instruction) => instruction,
+        Err(_) => return data
+    };
+
+    // mode digits on parameters the opcode doesn't have wouldn't survive reassembly
+    let parameter_count = instruction.op.parameter_count();
+    if instruction.modes[parameter_count..].iter().any(|mode| *mode != ParameterMode::Position) {
+        return data;
+    }
+    if address + parameter_count >= program.len() {
+        return data;
+    }
+    if let Some(parameter) = instruction.op.write_parameter() {
+        if instruction.modes[parameter - 1] == ParameterMode::Immediate {
+            return data;
+        }
+    }
+
+    let operands = (0..parameter_count)
+        .map(|i| Operand::new(instruction.modes[i], program[address + 1 + i]))
+        .collect();
+    Line { address, item: Item::Instruction { op: instruction.op, operands } }
+}
+
+/// Walks a program image from address 0, decoding one item after another.
+pub fn disassemble(program: &[i64]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut address = 0;
+    while address < program.len() {
+        let line = disassemble_at(program, address);
+        address += line.item.width();
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disasm::disassemble;
+    use crate::parse_program;
+
+    #[test]
+    fn listing() {
+        let program = parse_program("109,-1,21101,3,4,2,1005,7,12,99,42,-7");
+        let listing: Vec<String> = disassemble(&program).iter().map(|l| l.to_string()).collect();
+        assert_eq!(listing, vec![
+            "    0: ARB #-1",
+            "    2: ADD #3, #4, rb+2",
+            "    6: JNZ [7], #12",
+            "    9: HLT",
+            "   10: DATA 42",
+            "   11: DATA -7",
+        ]);
+    }
+
+    #[test]
+    fn truncated_and_immediate_writes_are_data() {
+        let program = parse_program("11101,1,2,3,1,0");
+        let listing: Vec<String> = disassemble(&program).iter().map(|l| l.to_string()).collect();
+        assert_eq!(listing, vec![
+            "    0: DATA 11101",
+            "    1: ADD [2], [3], [1]",
+            "    5: DATA 0",
+        ]);
+    }
+}
@@ -0,0 +1,103 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// A limit set on a `Machine` that stopped it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// Instructions executed, see `Machine::set_step_limit`.
+    Steps(u64),
+    /// Wall time of one `Machine::run`, see `Machine::set_time_limit`.
+    Time(Duration)
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Steps(steps) => write!(f, "step limit of {}", steps),
+            Limit::Time(time) => write!(f, "time limit of {:?}", time)
+        }
+    }
+}
+
+/// Where a machine was when a limit stopped it.
+///
+/// The machine is left in this state without running the instruction at `ip`, so it can be
+/// inspected or resumed after raising the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MachineState {
+    pub ip: i64,
+    pub relative_base: i64,
+    /// Instructions executed since the machine was loaded or restored.
+    pub steps: u64,
+    /// Backed memory cells, see `Memory::len`.
+    pub memory_cells: usize,
+    pub pending_inputs: usize
+}
+
+/// A fault in the program being run, reported instead of panicking the interpreter.
+///
+/// `ip` is the address of the faulting instruction and `opcode` the raw word found there.
+/// Parameters are numbered from 1 like in the puzzle descriptions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmError {
+    UnknownOpcode { ip: i64, opcode: i64 },
+    InvalidParameterMode { ip: i64, opcode: i64, parameter: usize },
+    ImmediateWrite { ip: i64, opcode: i64, parameter: usize },
+    NegativeAddress { ip: i64, opcode: i64, parameter: usize, address: i64 },
+    MemoryLimitExceeded { ip: i64, opcode: i64, parameter: usize, address: i64 },
+    IpOutOfRange { ip: i64 },
+    /// A result, or an address relative to the relative base, didn't fit the word type.
+    Overflow { ip: i64, opcode: i64 },
+    /// The program ran into a step or time limit. Running past the memory limit is a
+    /// `MemoryLimitExceeded` instead, which tells which access did it.
+    LimitExceeded { limit: Limit, state: MachineState }
+}
+
+impl VmError {
+    pub fn ip(&self) -> i64 {
+        match *self {
+            VmError::UnknownOpcode { ip, .. } => ip,
+            VmError::InvalidParameterMode { ip, .. } => ip,
+            VmError::ImmediateWrite { ip, .. } => ip,
+            VmError::NegativeAddress { ip, .. } => ip,
+            VmError::MemoryLimitExceeded { ip, .. } => ip,
+            VmError::IpOutOfRange { ip } => ip,
+            VmError::Overflow { ip, .. } => ip,
+            VmError::LimitExceeded { state, .. } => state.ip
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            VmError::UnknownOpcode { ip, opcode } => {
+                write!(f, "bad program at ip {}: opcode {}", ip, opcode)
+            },
+            VmError::InvalidParameterMode { ip, opcode, parameter } => {
+                write!(f, "bad program at ip {}: opcode {} has an invalid mode for parameter {}", ip, opcode, parameter)
+            },
+            VmError::ImmediateWrite { ip, opcode, parameter } => {
+                write!(f, "bad program at ip {}: opcode {} writes to parameter {} in immediate mode", ip, opcode, parameter)
+            },
+            VmError::NegativeAddress { ip, opcode, parameter, address } => {
+                write!(f, "bad program at ip {}: opcode {} parameter {} accesses negative address {}", ip, opcode, parameter, address)
+            },
+            VmError::MemoryLimitExceeded { ip, opcode, parameter, address } => {
+                write!(f, "bad program at ip {}: opcode {} parameter {} writes address {} past the memory limit", ip, opcode, parameter, address)
+            },
+            VmError::IpOutOfRange { ip } => {
+                write!(f, "bad program at ip {}: ran off the end of memory", ip)
+            },
+            VmError::Overflow { ip, opcode } => {
+                write!(f, "bad program at ip {}: opcode {} overflowed the word size", ip, opcode)
+            },
+            VmError::LimitExceeded { limit, state } => {
+                write!(f, "stopped at ip {} after {} instructions: {} reached", state.ip, state.steps, limit)
+            }
+        }
+    }
+}
+
+impl Error for VmError {}
@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+
+/// Why a day's input values couldn't be read, see `inputs_from_args`.
+#[derive(Debug)]
+pub enum InputsError {
+    BadValue(String),
+    MissingPath,
+    Io { path: String, error: io::Error }
+}
+
+impl fmt::Display for InputsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputsError::BadValue(value) => write!(f, "input `{}` isn't a number", value),
+            InputsError::MissingPath => write!(f, "-f needs a file name"),
+            InputsError::Io { path, error } => write!(f, "couldn't read inputs from {}: {}", path, error)
+        }
+    }
+}
+
+impl Error for InputsError {}
+
+fn parse_values(s: &str, inputs: &mut Vec<i64>) -> Result<(), InputsError> {
+    for value in s.split(|c: char| c == ',' || c.is_whitespace()).filter(|v| !v.is_empty()) {
+        inputs.push(value.parse().map_err(|_| InputsError::BadValue(value.to_string()))?);
+    }
+    Ok(())
+}
+
+/// Collects the values a day binary should queue for its program from its command line.
+///
+/// Each argument is a number or a comma separated list of numbers, `-f <file>` reads more values
+/// separated by commas or whitespace from a file. Values are queued in the order given.
+pub fn inputs_from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Vec<i64>, InputsError> {
+    let mut inputs = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "-f" {
+            let path = args.next().ok_or(InputsError::MissingPath)?;
+            let s = fs::read_to_string(&path).map_err(|error| InputsError::Io { path, error })?;
+            parse_values(&s, &mut inputs)?;
+        } else {
+            parse_values(&arg, &mut inputs)?;
+        }
+    }
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inputs::{inputs_from_args, InputsError};
+
+    fn args(s: &[&str]) -> Vec<String> {
+        s.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn values_and_lists() {
+        assert_eq!(inputs_from_args(args(&[])).unwrap(), Vec::<i64>::new());
+        assert_eq!(inputs_from_args(args(&["5"])).unwrap(), vec![5]);
+        assert_eq!(inputs_from_args(args(&["1,2", "-3"])).unwrap(), vec![1, 2, -3]);
+
+        match inputs_from_args(args(&["1", "x"])) {
+            Err(InputsError::BadValue(value)) => assert_eq!(value, "x"),
+            r => panic!("expected a bad value, got {:?}", r)
+        }
+        assert!(matches!(inputs_from_args(args(&["-f"])), Err(InputsError::MissingPath)));
+        assert!(matches!(inputs_from_args(args(&["-f", "/nonexistent"])), Err(InputsError::Io { .. })));
+    }
+}
@@ -0,0 +1,213 @@
+//! The instruction sets the puzzles introduced one after another.
+//!
+//! Day 2 only has `ADD`, `MUL` and `HLT` without parameter modes, day 5 adds opcodes 3 to 8 and
+//! immediate mode, day 9 adds `ARB` and relative mode. A `Machine` set to an older profile
+//! rejects anything newer, the way the interpreter of that day would have.
+
+use std::fmt;
+use std::str::FromStr;
+use crate::cfg::{Cfg, Exit};
+use crate::decode::{decode, DecodeError, Instruction, Opcode, ParameterMode};
+use crate::disasm::Item;
+use crate::trace::{TraceStep, Tracer};
+
+/// Machines default to `Day9`, which has everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum IsaProfile {
+    Day2,
+    Day5,
+    #[default]
+    Day9
+}
+
+impl IsaProfile {
+    pub const ALL: [IsaProfile; 3] = [IsaProfile::Day2, IsaProfile::Day5, IsaProfile::Day9];
+
+    pub fn has_opcode(self, op: Opcode) -> bool {
+        match op {
+            Opcode::Add | Opcode::Multiply | Opcode::Halt => true,
+            Opcode::AdjustRelativeBase => self >= IsaProfile::Day9,
+            _ => self >= IsaProfile::Day5
+        }
+    }
+
+    pub fn has_mode(self, mode: ParameterMode) -> bool {
+        match mode {
+            ParameterMode::Position => true,
+            ParameterMode::Immediate => self >= IsaProfile::Day5,
+            ParameterMode::Relative => self >= IsaProfile::Day9
+        }
+    }
+
+    /// Like `decode`, but only accepting what the profile has.
+    ///
+    /// Day 2 has no modes at all, so any word with mode digits is an unknown opcode there.
+    pub fn decode(self, word: i64) -> Result<Instruction, DecodeError> {
+        if self == IsaProfile::Day2 && word >= 100 {
+            return Err(DecodeError::UnknownOpcode);
+        }
+        let instruction = decode(word)?;
+        if !self.has_opcode(instruction.op) {
+            return Err(DecodeError::UnknownOpcode);
+        }
+        match instruction.modes.iter().position(|mode| !self.has_mode(*mode)) {
+            Some(i) => Err(DecodeError::InvalidParameterMode(i + 1)),
+            None => Ok(instruction)
+        }
+    }
+
+    /// The oldest profile that can run `instruction`.
+    ///
+    /// Like `decode`, this looks at the modes of all three parameters, even ones the opcode
+    /// doesn't have. `HLT` with a mode digit doesn't run on day 2.
+    pub fn required(instruction: &Instruction) -> IsaProfile {
+        IsaProfile::ALL.iter().copied()
+            .find(|profile| profile.has_opcode(instruction.op) && instruction.modes.iter().all(|mode| profile.has_mode(*mode)))
+            .unwrap_or(IsaProfile::Day9)
+    }
+}
+
+impl fmt::Display for IsaProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsaProfile::Day2 => write!(f, "day2"),
+            IsaProfile::Day5 => write!(f, "day5"),
+            IsaProfile::Day9 => write!(f, "day9")
+        }
+    }
+}
+
+impl FromStr for IsaProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        IsaProfile::ALL.iter().copied()
+            .find(|profile| profile.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown instruction set {:?}, expected day2, day5 or day9", s))
+    }
+}
+
+/// The profile a program needs.
+///
+/// `check_compatibility` works it out from the image, which misses code the program writes
+/// itself, like day 5's. Tracing a run with `Machine::run_traced` into a `Compatibility` sees
+/// exactly what that run executed instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compatibility {
+    pub profile: IsaProfile,
+    /// Address of the first instruction that needs `profile`, `None` if nothing needs more than
+    /// day 2.
+    pub needed_at: Option<i64>,
+    /// Reachable addresses that don't hold a valid instruction in the image.
+    pub invalid: Vec<usize>
+}
+
+impl Default for Compatibility {
+    fn default() -> Self {
+        Compatibility { profile: IsaProfile::Day2, needed_at: None, invalid: Vec::new() }
+    }
+}
+
+impl Compatibility {
+    fn add(&mut self, address: i64, instruction: &Instruction) {
+        let profile = IsaProfile::required(instruction);
+        if profile > self.profile {
+            self.profile = profile;
+            self.needed_at = Some(address);
+        }
+    }
+}
+
+impl Tracer for Compatibility {
+    fn trace(&mut self, step: &TraceStep) {
+        self.add(step.ip, &step.instruction);
+    }
+}
+
+/// The profile every instruction reachable from address 0 fits in.
+pub fn check_compatibility(program: &[i64]) -> Compatibility {
+    let mut compatibility = Compatibility::default();
+    for block in Cfg::new(program).blocks.values() {
+        for line in &block.lines {
+            if let Item::Instruction { op, operands } = &line.item {
+                let mut modes = [ParameterMode::Position; 3];
+                for (mode, operand) in modes.iter_mut().zip(operands) {
+                    *mode = operand.mode();
+                }
+                compatibility.add(line.address as i64, &Instruction { op: *op, modes });
+            }
+        }
+        // the listing stops at words like `HLT` with a mode digit, which still decode and run
+        if let Exit::Invalid(address) = block.exit {
+            match program.get(address).map(|word| decode(*word)) {
+                Some(Ok(instruction)) => compatibility.add(address as i64, &instruction),
+                _ => compatibility.invalid.push(address)
+            }
+        }
+    }
+    compatibility
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::isa::{check_compatibility, Compatibility, IsaProfile};
+    use crate::{parse_program, DecodeError, Machine, StopReason, VmError};
+
+    #[test]
+    fn profiles_reject_newer_instructions() {
+        assert!(IsaProfile::Day2.decode(2).is_ok());
+        assert_eq!(IsaProfile::Day2.decode(1002), Err(DecodeError::UnknownOpcode));
+        assert_eq!(IsaProfile::Day2.decode(3), Err(DecodeError::UnknownOpcode));
+        assert_eq!(IsaProfile::Day5.decode(1008).unwrap().encode(), 1008);
+        assert_eq!(IsaProfile::Day5.decode(9), Err(DecodeError::UnknownOpcode));
+        assert_eq!(IsaProfile::Day5.decode(2001), Err(DecodeError::InvalidParameterMode(2)));
+        assert_eq!(IsaProfile::Day9.decode(21201).unwrap().encode(), 21201);
+        assert_eq!("Day5".parse(), Ok(IsaProfile::Day5));
+    }
+
+    #[test]
+    fn unused_modes_count() {
+        // HLT with an immediate and a relative mode digit
+        for (word, profile) in &[(99, IsaProfile::Day2), (1099, IsaProfile::Day5), (20099, IsaProfile::Day9)] {
+            let instruction = IsaProfile::Day9.decode(*word).unwrap();
+            assert_eq!(IsaProfile::required(&instruction), *profile);
+            for older in IsaProfile::ALL.iter().filter(|p| *p < profile) {
+                assert!(older.decode(*word).is_err());
+            }
+            assert_eq!(check_compatibility(&[1, 0, 0, 0, *word]).profile, *profile);
+        }
+    }
+
+    #[test]
+    fn machines_run_their_profile() {
+        let program = parse_program("1101,2,3,0,99");
+        let mut m = Machine::new(&program);
+        m.set_profile(IsaProfile::Day2);
+        assert_eq!(m.run(), Err(VmError::UnknownOpcode { ip: 0, opcode: 1101 }));
+        m.set_profile(IsaProfile::Day5);
+        assert_eq!(m.run(), Ok(StopReason::Halted));
+        assert_eq!(m.read(0), 5);
+
+        let mut m = Machine::new(&parse_program("109,1,99"));
+        m.set_profile(IsaProfile::Day5);
+        assert_eq!(m.run(), Err(VmError::UnknownOpcode { ip: 0, opcode: 109 }));
+    }
+
+    #[test]
+    fn compatibility() {
+        assert_eq!(check_compatibility(&parse_program("1,9,10,3,2,3,11,0,99,30,40,50")), Compatibility::default());
+        assert_eq!(check_compatibility(&parse_program("1,0,0,0,3,0,1005,0,0,99")).profile, IsaProfile::Day5);
+
+        let day9 = check_compatibility(&parse_program("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99"));
+        assert_eq!((day9.profile, day9.needed_at), (IsaProfile::Day9, Some(0)));
+
+        // writes a relative mode output to 8 before running into it
+        let program = parse_program("1101,204,0,8,1101,0,1,9,0,0,99");
+        let image = check_compatibility(&program);
+        assert_eq!((image.profile, image.invalid), (IsaProfile::Day5, vec![8]));
+        let mut traced = Compatibility::default();
+        let mut m = Machine::new(&program);
+        while let StopReason::Output(_) = m.run_traced(&mut traced).unwrap() {}
+        assert_eq!((traced.profile, traced.needed_at), (IsaProfile::Day9, Some(8)));
+    }
+}
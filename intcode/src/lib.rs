@@ -0,0 +1,50 @@
+//! Shared Intcode interpreter used by every Intcode day.
+
+pub mod aot;
+mod ascii;
+pub mod cfg;
+pub mod coverage;
+mod decode;
+pub mod debugger;
+pub mod disasm;
+mod error;
+mod inputs;
+mod isa;
+mod machine;
+mod memory;
+pub mod network;
+mod patch;
+mod phases;
+pub mod scheduler;
+mod session;
+mod snapshot;
+pub mod solver;
+pub mod symbolic;
+mod translate;
+pub mod trace;
+mod word;
+
+pub use ascii::AsciiIo;
+pub use cfg::Cfg;
+pub use decode::{decode, DecodeError, Instruction, Opcode, ParameterMode};
+pub use disasm::disassemble;
+pub use error::{Limit, MachineState, VmError};
+pub use inputs::{inputs_from_args, InputsError};
+pub use isa::{check_compatibility, Compatibility, IsaProfile};
+pub use machine::{LastWrite, Machine, StopReason};
+pub use memory::{Memory, MemoryError, DEFAULT_MEMORY_LIMIT};
+pub use network::{Network, NetworkError};
+pub use patch::{Affine, Patch, PatchMatch, PatchSearch, DEFAULT_PATCH_STEP_LIMIT};
+pub use phases::{search_phases, Permutations, PhaseSearch};
+pub use scheduler::Scheduler;
+pub use session::{Event, Recorder, ReplayError, Session};
+pub use snapshot::{Snapshot, SnapshotError};
+pub use translate::translate;
+pub use trace::{TraceStep, Tracer};
+pub use word::Word;
+pub use num_bigint::BigInt;
+
+/// Parses the comma separated program format used by every day's `input.txt`.
+pub fn parse_program(s: &str) -> Vec<i64> {
+    s.trim_end().split(',').map(|s| s.trim().parse().unwrap()).collect()
+}
@@ -0,0 +1,830 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use crate::decode::{DecodeError, Instruction, Opcode, ParameterMode};
+use crate::isa::IsaProfile;
+use crate::memory::{Memory, MemoryError};
+use crate::snapshot::Snapshot;
+use crate::trace::{TraceStep, Tracer};
+use crate::word::Word;
+use crate::error::{Limit, MachineState, VmError};
+
+/// Why `Machine::run` handed control back to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason<W = i64> {
+    /// Opcode 3 was reached with an empty input queue. Push input and call `run` again.
+    NeedInput,
+    /// Opcode 4 produced a value.
+    Output(W),
+    /// Opcode 99 was reached.
+    Halted
+}
+
+/// The most recent write to a cell that is still in the undo log, see `Machine::last_write`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastWrite<W = i64> {
+    /// `Machine::steps` after the writing instruction, counting it.
+    pub steps: u64,
+    /// Address of the writing instruction.
+    pub ip: i64,
+    pub old: W,
+    pub new: W
+}
+
+/// What `Machine::step_back` needs to undo one executed instruction.
+#[derive(Debug, Clone)]
+struct UndoEntry<W> {
+    ip: i64,
+    relative_base: i64,
+    steps: u64,
+    /// Address, previous value and the memory length before the write.
+    write: Option<(i64, W, usize)>,
+    /// The value taken from the input queue.
+    input: Option<W>
+}
+
+/// An Intcode computer supporting opcodes 1-9 and 99.
+///
+/// Words are `i64` unless the machine is created with `load` for another `Word` type, like
+/// `BigInt`. Results that don't fit a word fail with `VmError::Overflow` rather than wrapping.
+/// Memory reads outside of the loaded program return 0, see `Memory`. Older programs can be held
+/// to the instruction set of their day with `set_profile`.
+///
+/// Runaway programs can be stopped with `set_step_limit`, `set_time_limit` and
+/// `set_memory_limit`, none of which are set except for the default memory limit.
+///
+/// With `set_undo_log` the machine remembers what the last instructions changed, so it can be run
+/// backwards with `step_back`.
+#[derive(Debug, Clone)]
+pub struct Machine<W = i64> {
+    memory: Memory<W>,
+    ip: i64,
+    relative_base: i64,
+    inputs: VecDeque<W>,
+    decode_cache: Option<Vec<Option<Instruction>>>,
+    profile: IsaProfile,
+    steps: u64,
+    step_limit: Option<u64>,
+    time_limit: Option<Duration>,
+    /// Entries of the most recent instructions, at most `undo_depth` of them.
+    undo_log: Option<VecDeque<UndoEntry<W>>>,
+    undo_depth: usize
+}
+
+/// Executed instructions between checks of the time limit, reading the clock takes a while.
+const TIME_CHECK_INTERVAL: u64 = 1024;
+
+impl Machine {
+    pub fn new(program: &[i64]) -> Self {
+        Machine::load(program)
+    }
+
+    /// Captures memory, ip, relative base and queued inputs so the machine can be resumed later.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.as_slice().to_vec(),
+            memory_limit: self.memory.limit(),
+            ip: self.ip,
+            relative_base: self.relative_base,
+            inputs: self.inputs.iter().copied().collect()
+        }
+    }
+
+    /// Puts the machine back into the state `snapshot` was taken in.
+    ///
+    /// Settings of this machine, like whether decoded instructions are cached or the step limit,
+    /// are kept. The step count starts over.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.memory = Memory::new(&snapshot.memory);
+        self.memory.set_limit(snapshot.memory_limit);
+        self.ip = snapshot.ip;
+        self.relative_base = snapshot.relative_base;
+        self.inputs = snapshot.inputs.iter().copied().collect();
+        self.steps = 0;
+        if let Some(cache) = self.decode_cache.as_mut() {
+            cache.clear();
+        }
+        if let Some(log) = self.undo_log.as_mut() {
+            log.clear();
+        }
+    }
+
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        let mut m = Machine::new(&[]);
+        m.restore(snapshot);
+        m
+    }
+
+    /// Like `run`, but reports every executed instruction to `tracer` and then `Tracer::stopped`.
+    pub fn run_traced(&mut self, tracer: &mut dyn Tracer) -> Result<StopReason, VmError> {
+        let started = Instant::now();
+        let result = loop {
+            match self.step_traced(tracer) {
+                Ok(Some(reason)) => break Ok(reason),
+                Ok(None) => {},
+                Err(e) => break Err(e)
+            }
+            if let Err(e) = self.check_time(started) {
+                break Err(e);
+            }
+        };
+        tracer.stopped(self.ip);
+        result
+    }
+
+    /// Like `step`, but reports the instruction to `tracer` if it executed.
+    ///
+    /// An input instruction waiting for a value and faulting instructions aren't reported.
+    pub fn step_traced(&mut self, tracer: &mut dyn Tracer) -> Result<Option<StopReason>, VmError> {
+        let ip = self.ip;
+        let relative_base = self.relative_base;
+        let instruction = self.fetch()?;
+        let opcode = self.read(ip);
+
+        let mut operands = [0; 3];
+        for (i, operand) in operands.iter_mut().enumerate().take(instruction.op.parameter_count()) {
+            *operand = self.read(ip + 1 + i as i64);
+        }
+        // the written address has to be worked out before the instruction can change it. If it
+        // overflows, `step` fails with `VmError::Overflow` and nothing is traced.
+        let write_address = instruction.op.write_parameter().and_then(|parameter| match instruction.modes[parameter - 1] {
+            ParameterMode::Relative => operands[parameter - 1].checked_add(relative_base),
+            _ => Some(operands[parameter - 1])
+        });
+
+        let result = self.step()?;
+        if result == Some(StopReason::NeedInput) {
+            return Ok(result);
+        }
+
+        tracer.trace(&TraceStep {
+            ip,
+            opcode,
+            instruction,
+            operands,
+            relative_base,
+            write: write_address.map(|address| (address, self.read(address)))
+        });
+        Ok(result)
+    }
+}
+
+impl<W: Word> Machine<W> {
+    /// A machine computing with words of type `W`, e.g. `Machine::<BigInt>::load(&program)`.
+    pub fn load(program: &[i64]) -> Self {
+        Machine {
+            memory: Memory::from_words(program.iter().map(|word| W::from_i64(*word)).collect()),
+            ip: 0,
+            relative_base: 0,
+            inputs: VecDeque::new(),
+            decode_cache: None,
+            profile: IsaProfile::default(),
+            steps: 0,
+            step_limit: None,
+            time_limit: None,
+            undo_log: None,
+            undo_depth: 0
+        }
+    }
+
+    pub fn ip(&self) -> i64 {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn memory(&self) -> &Memory<W> {
+        &self.memory
+    }
+
+    /// Sets the maximum number of memory cells the program may use, see `Memory::set_limit`.
+    pub fn set_memory_limit(&mut self, limit: usize) {
+        self.memory.set_limit(limit);
+    }
+
+    /// Reads a memory cell. Panics on a negative address.
+    pub fn read(&self, address: i64) -> W {
+        self.memory.get(address).unwrap()
+    }
+
+    /// Writes a memory cell. Panics on a negative address or one past the memory limit.
+    pub fn write(&mut self, address: i64, value: W) {
+        self.set_cell(address, value).unwrap();
+    }
+
+    /// Turns caching of decoded instructions by address on or off.
+    ///
+    /// Cached entries are dropped whenever their opcode word is written, so self-modifying
+    /// programs still see their own changes.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decode_cache = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    pub fn profile(&self) -> IsaProfile {
+        self.profile
+    }
+
+    /// Makes the machine reject opcodes and modes `profile` doesn't have, as `VmError::UnknownOpcode`
+    /// and `VmError::InvalidParameterMode`. Machines start out with `IsaProfile::Day9`, which has
+    /// everything.
+    pub fn set_profile(&mut self, profile: IsaProfile) {
+        self.profile = profile;
+        if let Some(cache) = self.decode_cache.as_mut() {
+            cache.clear();
+        }
+    }
+
+    /// Instructions executed since the machine was loaded or restored.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Stops the machine with `VmError::LimitExceeded` before it executes more than `limit`
+    /// instructions in total, counted by `steps`. `None` lets it run forever.
+    pub fn set_step_limit(&mut self, limit: Option<u64>) {
+        self.step_limit = limit;
+    }
+
+    /// Stops `run` with `VmError::LimitExceeded` once a single call has taken longer than
+    /// `limit`. The clock is only read every 1024 instructions, so the limit can be overshot
+    /// slightly.
+    pub fn set_time_limit(&mut self, limit: Option<Duration>) {
+        self.time_limit = limit;
+    }
+
+    /// Keeps an undo log of the last `depth` instructions, `None` turns it off. Turning it on
+    /// starts with an empty log, so `step_back` can only go back to the state the machine is in
+    /// now. Making it shorter forgets the oldest entries.
+    ///
+    /// The log keeps one entry per executed instruction, with the ip, relative base, overwritten
+    /// cell and consumed input from before it. Outputs can't be taken back, stepping back over
+    /// an output instruction only moves the ip.
+    pub fn set_undo_log(&mut self, depth: Option<usize>) {
+        match depth {
+            Some(depth) => {
+                let log = self.undo_log.get_or_insert_with(VecDeque::new);
+                while log.len() > depth {
+                    log.pop_front();
+                }
+                self.undo_depth = depth;
+            },
+            None => {
+                self.undo_log = None;
+                self.undo_depth = 0;
+            }
+        }
+    }
+
+    /// The `depth` given to `set_undo_log`, `None` if the undo log is off.
+    pub fn undo_depth(&self) -> Option<usize> {
+        self.undo_log.as_ref().map(|_| self.undo_depth)
+    }
+
+    /// Instructions `step_back` can undo.
+    pub fn undoable_steps(&self) -> usize {
+        self.undo_log.as_ref().map_or(0, |log| log.len())
+    }
+
+    /// Undoes the last executed instruction: memory, ip, relative base and step count go back to
+    /// what they were and a consumed input is queued again in front of the others. Returns
+    /// `false` if the undo log is off or empty.
+    pub fn step_back(&mut self) -> bool {
+        let entry = match self.undo_log.as_mut().and_then(|log| log.pop_back()) {
+            Some(entry) => entry,
+            None => return false
+        };
+        if let Some((address, old, len)) = entry.write {
+            self.set_cell(address, old).unwrap();
+            self.memory.truncate(len);
+        }
+        if let Some(value) = entry.input {
+            self.inputs.push_front(value);
+        }
+        self.ip = entry.ip;
+        self.relative_base = entry.relative_base;
+        self.steps = entry.steps;
+        true
+    }
+
+    /// The last write to `address` the undo log still has.
+    pub fn last_write(&self, address: i64) -> Option<LastWrite<W>> {
+        let log = self.undo_log.as_ref()?;
+        log.iter().rev().find_map(|entry| match &entry.write {
+            Some((written, old, _)) if *written == address => Some(LastWrite {
+                steps: entry.steps + 1,
+                ip: entry.ip,
+                old: old.clone(),
+                new: self.read(address)
+            }),
+            _ => None
+        })
+    }
+
+    /// The state `VmError::LimitExceeded` reports.
+    pub fn state(&self) -> MachineState {
+        MachineState {
+            ip: self.ip,
+            relative_base: self.relative_base,
+            steps: self.steps,
+            memory_cells: self.memory.len(),
+            pending_inputs: self.inputs.len()
+        }
+    }
+
+    #[inline]
+    fn check_time(&self, started: Instant) -> Result<(), VmError> {
+        if let Some(limit) = self.time_limit {
+            if self.steps.is_multiple_of(TIME_CHECK_INTERVAL) && started.elapsed() > limit {
+                return Err(VmError::LimitExceeded { limit: Limit::Time(limit), state: self.state() });
+            }
+        }
+        Ok(())
+    }
+
+    fn set_cell(&mut self, address: i64, value: W) -> Result<(), MemoryError> {
+        self.memory.set(address, value)?;
+        if let Some(cache) = self.decode_cache.as_mut() {
+            if let Some(entry) = cache.get_mut(address as usize) {
+                *entry = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn fetch(&mut self) -> Result<Instruction, VmError> {
+        if self.ip < 0 || self.ip as usize >= self.memory.len() {
+            return Err(VmError::IpOutOfRange { ip: self.ip });
+        }
+        let ip = self.ip as usize;
+
+        if let Some(Some(instruction)) = self.decode_cache.as_ref().and_then(|cache| cache.get(ip)) {
+            return Ok(*instruction);
+        }
+
+        let opcode = self.read(self.ip).saturating_i64();
+        let instruction = self.profile.decode(opcode).map_err(|e| match e {
+            DecodeError::UnknownOpcode => VmError::UnknownOpcode { ip: self.ip, opcode },
+            DecodeError::InvalidParameterMode(parameter) => VmError::InvalidParameterMode { ip: self.ip, opcode, parameter }
+        })?;
+
+        if let Some(cache) = self.decode_cache.as_mut() {
+            if cache.len() <= ip {
+                cache.resize(self.memory.len(), None);
+            }
+            cache[ip] = Some(instruction);
+        }
+        Ok(instruction)
+    }
+
+    /// Queues a value for the next opcode 3.
+    pub fn push_input(&mut self, value: W) {
+        self.inputs.push_back(value);
+    }
+
+    /// Number of queued values opcode 3 hasn't read yet.
+    pub fn pending_inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn address_error(&self, offset: i64, e: MemoryError) -> VmError {
+        let opcode = self.read(self.ip).saturating_i64();
+        let parameter = offset as usize;
+        match e {
+            MemoryError::NegativeAddress(address) => {
+                VmError::NegativeAddress { ip: self.ip, opcode, parameter, address }
+            },
+            MemoryError::LimitExceeded(address) => {
+                VmError::MemoryLimitExceeded { ip: self.ip, opcode, parameter, address }
+            }
+        }
+    }
+
+    fn overflow(&self) -> VmError {
+        VmError::Overflow { ip: self.ip, opcode: self.read(self.ip).saturating_i64() }
+    }
+
+    /// `value` added to the relative base.
+    fn relative(&self, value: &W) -> Result<i64, VmError> {
+        value.saturating_i64().checked_add(self.relative_base).ok_or_else(|| self.overflow())
+    }
+
+    fn get_value(&self, offset: i64, p: ParameterMode) -> Result<W, VmError> {
+        let parameter = self.read(self.ip + offset);
+        let address = match p {
+            ParameterMode::Position => parameter.saturating_i64(),
+            ParameterMode::Immediate => return Ok(parameter),
+            ParameterMode::Relative => self.relative(&parameter)?
+        };
+        self.memory.get(address).map_err(|e| self.address_error(offset, e))
+    }
+
+    fn store_value(&mut self, offset: i64, value: W, p: ParameterMode) -> Result<(), VmError> {
+        let parameter = self.read(self.ip + offset);
+        let address = match p {
+            ParameterMode::Position => parameter.saturating_i64(),
+            ParameterMode::Relative => self.relative(&parameter)?,
+            ParameterMode::Immediate => {
+                let opcode = self.read(self.ip).saturating_i64();
+                return Err(VmError::ImmediateWrite { ip: self.ip, opcode, parameter: offset as usize });
+            }
+        };
+        if let Some(entry) = self.undo_log.as_mut().and_then(|log| log.back_mut()) {
+            if address >= 0 {
+                entry.write = Some((address, self.memory.get(address).unwrap(), self.memory.len()));
+            }
+        }
+        self.set_cell(address, value).map_err(|e| self.address_error(offset, e))
+    }
+
+    /// Runs the program until it halts, produces an output or needs input that hasn't been pushed.
+    ///
+    /// The machine can be resumed by calling `run` again. After `Halted` every further call
+    /// returns `Halted` again. On error the machine is left on the faulting instruction.
+    pub fn run(&mut self) -> Result<StopReason<W>, VmError> {
+        let started = Instant::now();
+        loop {
+            if let Some(reason) = self.step()? {
+                return Ok(reason);
+            }
+            self.check_time(started)?;
+        }
+    }
+
+    /// Executes a single instruction.
+    ///
+    /// Returns the reason `run` would have stopped after this instruction, if any. Like `run`,
+    /// the machine doesn't move when it needs input or has halted. Waiting for input doesn't
+    /// count as a step.
+    #[inline]
+    pub fn step(&mut self) -> Result<Option<StopReason<W>>, VmError> {
+        if let Some(limit) = self.step_limit {
+            if self.steps >= limit {
+                return Err(VmError::LimitExceeded { limit: Limit::Steps(limit), state: self.state() });
+            }
+        }
+        if let Some(log) = self.undo_log.as_mut() {
+            log.push_back(UndoEntry { ip: self.ip, relative_base: self.relative_base, steps: self.steps, write: None, input: None });
+        }
+        let result = self.execute();
+        if let Some(log) = self.undo_log.as_mut() {
+            // instructions that didn't change anything have nothing to undo
+            if !matches!(result, Ok(None) | Ok(Some(StopReason::Output(_)))) {
+                log.pop_back();
+            }
+            if log.len() > self.undo_depth {
+                log.pop_front();
+            }
+        }
+        let result = result?;
+        if !matches!(result, Some(StopReason::NeedInput)) {
+            self.steps += 1;
+        }
+        Ok(result)
+    }
+
+    #[inline]
+    fn execute(&mut self) -> Result<Option<StopReason<W>>, VmError> {
+        let instruction = self.fetch()?;
+        let [param_first, param_second, param_third] = instruction.modes;
+
+        match instruction.op {
+            Opcode::Add => {
+                let first_value = self.get_value(1, param_first)?;
+                let second_value = self.get_value(2, param_second)?;
+
+                let sum = first_value.try_add(&second_value).ok_or_else(|| self.overflow())?;
+                self.store_value(3, sum, param_third)?;
+                self.ip += 4;
+            },
+            Opcode::Multiply => {
+                let first_value = self.get_value(1, param_first)?;
+                let second_value = self.get_value(2, param_second)?;
+
+                let product = first_value.try_mul(&second_value).ok_or_else(|| self.overflow())?;
+                self.store_value(3, product, param_third)?;
+                self.ip += 4;
+            },
+            Opcode::Input => {
+                match self.inputs.pop_front() {
+                    Some(value) => {
+                        if let Some(entry) = self.undo_log.as_mut().and_then(|log| log.back_mut()) {
+                            entry.input = Some(value.clone());
+                        }
+                        self.store_value(1, value, param_first)?;
+                        self.ip += 2;
+                    },
+                    None => return Ok(Some(StopReason::NeedInput))
+                }
+            },
+            Opcode::Output => {
+                let value = self.get_value(1, param_first)?;
+                self.ip += 2;
+                return Ok(Some(StopReason::Output(value)));
+            },
+            Opcode::JumpIfTrue => {
+                let first_value = self.get_value(1, param_first)?;
+                let second_value = self.get_value(2, param_second)?;
+
+                if !first_value.is_zero() {
+                    self.ip = second_value.saturating_i64();
+                } else {
+                    self.ip += 3;
+                }
+            },
+            Opcode::JumpIfFalse => {
+                let first_value = self.get_value(1, param_first)?;
+                let second_value = self.get_value(2, param_second)?;
+
+                if first_value.is_zero() {
+                    self.ip = second_value.saturating_i64();
+                } else {
+                    self.ip += 3;
+                }
+            },
+            Opcode::LessThan => {
+                let first_value = self.get_value(1, param_first)?;
+                let second_value = self.get_value(2, param_second)?;
+
+                self.store_value(3, W::from_i64((first_value < second_value) as i64), param_third)?;
+                self.ip += 4;
+            },
+            Opcode::Equals => {
+                let first_value = self.get_value(1, param_first)?;
+                let second_value = self.get_value(2, param_second)?;
+
+                self.store_value(3, W::from_i64((first_value == second_value) as i64), param_third)?;
+                self.ip += 4;
+            },
+            Opcode::AdjustRelativeBase => {
+                let value = self.get_value(1, param_first)?;
+                self.relative_base = self.relative(&value)?;
+                self.ip += 2;
+            },
+            Opcode::Halt => return Ok(Some(StopReason::Halted))
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{parse_program, BigInt, LastWrite, Limit, Machine, MachineState, StopReason, TraceStep, VmError};
+
+    fn run_with_input(program: &str, input: i64) -> Vec<i64> {
+        let mut m = Machine::new(&parse_program(program));
+        m.push_input(input);
+
+        let mut outputs = Vec::new();
+        while let StopReason::Output(value) = m.run().unwrap() {
+            outputs.push(value);
+        }
+        outputs
+    }
+
+    #[test]
+    fn day2_example() {
+        let mut m = Machine::new(&parse_program("1,9,10,3,2,3,11,0,99,30,40,50"));
+        assert_eq!(m.run().unwrap(), StopReason::Halted);
+        assert_eq!(m.read(0), 3500);
+        assert_eq!(m.run().unwrap(), StopReason::Halted);
+    }
+
+    #[test]
+    fn day5_compare() {
+        let program = "3,9,8,9,10,9,4,9,99,-1,8";
+        assert_eq!(run_with_input(program, 8), vec![1]);
+        assert_eq!(run_with_input(program, 7), vec![0]);
+    }
+
+    #[test]
+    fn day5_jumps() {
+        let program = "3,3,1105,-1,9,1101,0,0,12,4,12,99,1";
+        assert_eq!(run_with_input(program, 0), vec![0]);
+        assert_eq!(run_with_input(program, 5), vec![1]);
+    }
+
+    #[test]
+    fn day9_quine() {
+        let program = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        assert_eq!(run_with_input(program, 0), parse_program(program));
+    }
+
+    #[test]
+    fn day9_large_numbers() {
+        assert_eq!(run_with_input("104,1125899906842624,99", 0), vec![1125899906842624]);
+        assert_eq!(run_with_input("1102,34915192,34915192,7,4,7,99,0", 0), vec![1219070632396864]);
+    }
+
+    #[test]
+    fn relative_mode_store() {
+        // rb = 10, then input stored at rb+2
+        assert_eq!(run_with_input("109,10,203,2,204,2,99", 42), vec![42]);
+    }
+
+    #[test]
+    fn resume_after_input() {
+        // echo inputs until a 0 is given
+        let mut m = Machine::new(&parse_program("3,11,4,11,1005,11,0,99,0,0,0,0"));
+        assert_eq!(m.run().unwrap(), StopReason::NeedInput);
+        assert_eq!(m.run().unwrap(), StopReason::NeedInput);
+        m.push_input(7);
+        assert_eq!(m.run().unwrap(), StopReason::Output(7));
+        assert_eq!(m.run().unwrap(), StopReason::NeedInput);
+        m.push_input(0);
+        assert_eq!(m.run().unwrap(), StopReason::Output(0));
+        assert_eq!(m.run().unwrap(), StopReason::Halted);
+    }
+
+    #[test]
+    fn day7_feedback_loop() {
+        let program = parse_program("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5");
+        let mut amplifiers: Vec<Machine> = [9, 8, 7, 6, 5].iter().map(|phase| {
+            let mut m = Machine::new(&program);
+            m.push_input(*phase);
+            m
+        }).collect();
+
+        let mut signal = 0;
+        'feedback: loop {
+            for m in amplifiers.iter_mut() {
+                m.push_input(signal);
+                match m.run().unwrap() {
+                    StopReason::Output(value) => signal = value,
+                    StopReason::Halted => break 'feedback,
+                    StopReason::NeedInput => unreachable!()
+                }
+            }
+        }
+        assert_eq!(signal, 139629729);
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        let mut m = Machine::new(&parse_program("1101,1,1,5,42,0"));
+        let e = m.run().unwrap_err();
+        assert_eq!(e, VmError::UnknownOpcode { ip: 4, opcode: 42 });
+        assert_eq!(e.to_string(), "bad program at ip 4: opcode 42");
+        assert_eq!(m.ip(), 4);
+    }
+
+    #[test]
+    fn invalid_parameter_mode() {
+        let mut m = Machine::new(&parse_program("3001,0,0,0,99"));
+        assert_eq!(m.run(), Err(VmError::InvalidParameterMode { ip: 0, opcode: 3001, parameter: 2 }));
+    }
+
+    #[test]
+    fn immediate_write() {
+        let mut m = Machine::new(&parse_program("11101,1,1,0,99"));
+        assert_eq!(m.run(), Err(VmError::ImmediateWrite { ip: 0, opcode: 11101, parameter: 3 }));
+    }
+
+    #[test]
+    fn ran_off_the_end() {
+        let mut m = Machine::new(&parse_program("1101,1,1,0"));
+        assert_eq!(m.run(), Err(VmError::IpOutOfRange { ip: 4 }));
+    }
+
+    #[test]
+    fn bad_addresses() {
+        let mut m = Machine::new(&parse_program("1,-3,0,0,99"));
+        assert_eq!(m.run(), Err(VmError::NegativeAddress { ip: 0, opcode: 1, parameter: 1, address: -3 }));
+
+        let mut m = Machine::new(&parse_program("109,-5,22201,0,0,0,99"));
+        assert_eq!(m.run(), Err(VmError::NegativeAddress { ip: 2, opcode: 22201, parameter: 1, address: -5 }));
+
+        let mut m = Machine::new(&parse_program("1101,1,1,100,99"));
+        m.set_memory_limit(64);
+        assert_eq!(m.run(), Err(VmError::MemoryLimitExceeded { ip: 0, opcode: 1101, parameter: 3, address: 100 }));
+    }
+
+    #[test]
+    fn overflow() {
+        // 2^62 * 4
+        let program = parse_program("1102,4611686018427387904,4,7,4,7,99,0");
+        let mut m = Machine::new(&program);
+        assert_eq!(m.run(), Err(VmError::Overflow { ip: 0, opcode: 1102 }));
+
+        let mut m = Machine::<BigInt>::load(&program);
+        let two_to_the_64 = BigInt::from(u64::MAX) + 1u32;
+        assert_eq!(m.run(), Ok(StopReason::Output(two_to_the_64.clone())));
+        assert_eq!(m.read(7), two_to_the_64);
+        assert_eq!(m.run(), Ok(StopReason::Halted));
+
+        let mut m = Machine::new(&parse_program("109,9223372036854775807,109,1,99"));
+        assert_eq!(m.run(), Err(VmError::Overflow { ip: 2, opcode: 109 }));
+    }
+
+    #[test]
+    fn traced_overflow() {
+        let mut m = Machine::new(&[109, i64::MAX, 21101, 1, 1, 5, 99]);
+        let mut steps = 0;
+        assert_eq!(m.run_traced(&mut |_: &TraceStep| steps += 1), Err(VmError::Overflow { ip: 2, opcode: 21101 }));
+        assert_eq!(steps, 1);
+    }
+
+    #[test]
+    fn step_limit() {
+        // counts at [20] forever, after reading an input
+        let mut m = Machine::new(&parse_program("3,20,1001,20,1,20,1105,1,2"));
+        m.set_step_limit(Some(10));
+        assert_eq!(m.run(), Ok(StopReason::NeedInput));
+        m.push_input(5);
+        let state = MachineState { ip: 6, relative_base: 0, steps: 10, memory_cells: 21, pending_inputs: 0 };
+        let e = m.run().unwrap_err();
+        assert_eq!(e, VmError::LimitExceeded { limit: Limit::Steps(10), state });
+        assert_eq!(e.to_string(), "stopped at ip 6 after 10 instructions: step limit of 10 reached");
+        assert_eq!((m.state(), m.read(20)), (state, 10));
+
+        m.set_step_limit(Some(12));
+        assert!(m.run().is_err());
+        assert_eq!((m.ip(), m.read(20)), (6, 11));
+    }
+
+    #[test]
+    fn time_limit() {
+        let mut m = Machine::new(&parse_program("1105,1,0"));
+        m.set_time_limit(Some(Duration::from_millis(20)));
+        match m.run() {
+            Err(VmError::LimitExceeded { limit: Limit::Time(_), state }) => {
+                assert_eq!(state.ip, 0);
+                assert!(state.steps > 0 && state.steps.is_multiple_of(1024));
+            },
+            other => panic!("{:?}", other)
+        }
+        assert_eq!(Machine::new(&parse_program("99")).run(), Ok(StopReason::Halted));
+    }
+
+    #[test]
+    fn big_words_run_day9() {
+        let program = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let mut m = Machine::<BigInt>::load(&parse_program(program));
+        let mut outputs = Vec::new();
+        while let StopReason::Output(value) = m.run().unwrap() {
+            outputs.push(value);
+        }
+        let expected: Vec<BigInt> = parse_program(program).into_iter().map(BigInt::from).collect();
+        assert_eq!(outputs, expected);
+    }
+
+    #[test]
+    fn decode_cache_sees_self_modifying_writes() {
+        // adds 2 and 3, then rewrites its first instruction into a multiply and runs it again
+        let program = "1101,2,3,30,4,30,1005,31,21,1101,1102,0,0,1101,1,0,31,1105,1,0,0,99";
+        let mut m = Machine::new(&parse_program(program));
+        m.set_decode_cache(true);
+
+        let mut outputs = Vec::new();
+        while let StopReason::Output(value) = m.run().unwrap() {
+            outputs.push(value);
+        }
+        assert_eq!(outputs, vec![5, 6]);
+        assert_eq!(run_with_input(program, 0), vec![5, 6]);
+    }
+
+    #[test]
+    fn step_back() {
+        // reads an input into [11], adds [9] to it and writes the sum past the end at [20]
+        let program = parse_program("3,11,109,5,20001,9,11,15,99,7,0,0");
+        let mut m = Machine::new(&program);
+        m.set_undo_log(Some(10));
+        assert!(!m.step_back());
+        m.push_input(4);
+        assert_eq!(m.run(), Ok(StopReason::Halted));
+        assert_eq!((m.read(20), m.memory().len(), m.undoable_steps()), (11, 21, 3));
+        assert_eq!(m.last_write(20), Some(LastWrite { steps: 3, ip: 4, old: 0, new: 11 }));
+        assert_eq!(m.last_write(11), Some(LastWrite { steps: 1, ip: 0, old: 0, new: 4 }));
+        assert_eq!(m.last_write(9), None);
+
+        assert!(m.step_back());
+        assert_eq!((m.ip(), m.relative_base(), m.steps(), m.memory().len()), (4, 5, 2, 12));
+        assert!(m.step_back() && m.step_back());
+        assert!(!m.step_back());
+        assert_eq!((m.ip(), m.relative_base(), m.steps(), m.pending_inputs()), (0, 0, 0, 1));
+        assert_eq!(m.memory().as_slice(), program.as_slice());
+
+        // running forward again reads the same input
+        assert_eq!(m.run(), Ok(StopReason::Halted));
+        assert_eq!(m.read(20), 11);
+
+        // a shorter log only goes back so far
+        m.set_undo_log(Some(2));
+        assert_eq!((m.undo_depth(), m.undoable_steps()), (Some(2), 2));
+        assert!(m.step_back() && m.step_back());
+        assert!(!m.step_back());
+        assert_eq!((m.ip(), m.steps(), m.read(11)), (2, 1, 4));
+        assert_eq!(m.last_write(11), None);
+
+        let mut m = Machine::new(&parse_program("1105,1,0"));
+        m.set_undo_log(Some(100));
+        for _ in 0..1000 {
+            m.step().unwrap();
+        }
+        assert_eq!(m.undoable_steps(), 100);
+        m.set_undo_log(None);
+        assert_eq!((m.undo_depth(), m.undoable_steps()), (None, 0));
+        assert!(!m.step_back());
+    }
+}
@@ -0,0 +1,114 @@
+use crate::word::Word;
+
+/// Default cap on how many cells a program may grow memory to (128 MiB of words).
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 24;
+
+/// Why a memory access was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryError {
+    NegativeAddress(i64),
+    LimitExceeded(i64)
+}
+
+/// Flat Intcode memory that grows on write.
+///
+/// Every cell that was never written reads as 0. Writes past the end grow the backing `Vec` as
+/// long as the address stays below the memory limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Memory<W = i64> {
+    cells: Vec<W>,
+    limit: usize
+}
+
+impl Memory {
+    pub fn new(program: &[i64]) -> Self {
+        Memory::from_words(program.to_vec())
+    }
+}
+
+impl<W: Word> Memory<W> {
+    /// Memory holding `words`, for word types other than `i64`.
+    pub fn from_words(words: Vec<W>) -> Self {
+        Memory {
+            limit: DEFAULT_MEMORY_LIMIT.max(words.len()),
+            cells: words
+        }
+    }
+
+    /// Number of cells currently backed, the loaded program plus anything written past it.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Sets the maximum number of cells. Memory that already grew past the new limit is kept.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    pub fn as_slice(&self) -> &[W] {
+        &self.cells
+    }
+
+    pub fn get(&self, address: i64) -> Result<W, MemoryError> {
+        if address < 0 {
+            return Err(MemoryError::NegativeAddress(address));
+        }
+        Ok(self.cells.get(address as usize).cloned().unwrap_or_default())
+    }
+
+    pub fn set(&mut self, address: i64, value: W) -> Result<(), MemoryError> {
+        if address < 0 {
+            return Err(MemoryError::NegativeAddress(address));
+        }
+
+        let address_index = address as usize;
+        if address_index >= self.cells.len() {
+            if address_index >= self.limit {
+                return Err(MemoryError::LimitExceeded(address));
+            }
+            self.cells.resize(address_index + 1, W::default());
+        }
+        self.cells[address_index] = value;
+        Ok(())
+    }
+
+    /// Drops cells from `len` on, undoing growth from writes past the end.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.cells.truncate(len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::{Memory, MemoryError};
+
+    #[test]
+    fn zero_fill() {
+        let mut m = Memory::new(&[1, 2, 3]);
+        assert_eq!(m.get(100), Ok(0));
+        assert_eq!(m.len(), 3);
+
+        m.set(10, 7).unwrap();
+        assert_eq!(m.len(), 11);
+        assert_eq!(m.get(9), Ok(0));
+        assert_eq!(m.get(10), Ok(7));
+    }
+
+    #[test]
+    fn bad_addresses() {
+        let mut m = Memory::new(&[1, 2, 3]);
+        m.set_limit(8);
+        assert_eq!(m.get(-1), Err(MemoryError::NegativeAddress(-1)));
+        assert_eq!(m.set(-1, 0), Err(MemoryError::NegativeAddress(-1)));
+        assert_eq!(m.set(8, 0), Err(MemoryError::LimitExceeded(8)));
+        assert_eq!(m.set(7, 0), Ok(()));
+    }
+}
@@ -0,0 +1,250 @@
+//! Several machines wired together, like day7's amplifiers.
+//!
+//! Nodes either send every output to the nodes they're linked to, or, in addressed mode, group
+//! their outputs into packets whose first word is the destination node.
+
+use std::error::Error;
+use std::fmt;
+use crate::error::VmError;
+use crate::machine::Machine;
+use crate::scheduler::{Outbox, RunOutcome, Scheduler, TaskState};
+
+pub type NodeId = usize;
+
+/// A packet sent to an address outside of an addressed network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub source: NodeId,
+    pub destination: i64,
+    pub words: Vec<i64>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkError {
+    Vm { node: NodeId, error: VmError },
+    /// Every node that hasn't halted is waiting for input no other node is going to send.
+    Deadlock { blocked: Vec<NodeId> }
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::Vm { node, error } => write!(f, "node {}: {}", node, error),
+            NetworkError::Deadlock { blocked } => write!(f, "deadlock, nodes {:?} are all waiting for input", blocked)
+        }
+    }
+}
+
+impl Error for NetworkError {}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    links: Vec<NodeId>,
+    outputs: Vec<i64>,
+    /// Outputs of a packet that isn't complete yet, addressed mode only.
+    packet: Vec<i64>
+}
+
+/// Where outputs go, kept apart from the scheduler so it can be borrowed while that runs.
+#[derive(Debug, Clone, Default)]
+struct Routes {
+    nodes: Vec<Node>,
+    /// Words per packet after the address, set in addressed mode.
+    packet_size: Option<usize>,
+    external: Vec<Packet>
+}
+
+impl Routes {
+    fn route(&mut self, source: NodeId, value: i64, outbox: &mut Outbox) {
+        self.nodes[source].outputs.push(value);
+
+        let packet_size = match self.packet_size {
+            Some(packet_size) => packet_size,
+            None => {
+                for to in &self.nodes[source].links {
+                    outbox.send(*to, value);
+                }
+                return;
+            }
+        };
+
+        self.nodes[source].packet.push(value);
+        if self.nodes[source].packet.len() < packet_size + 1 {
+            return;
+        }
+        let mut words = std::mem::take(&mut self.nodes[source].packet);
+        let destination = words.remove(0);
+        if destination >= 0 && (destination as usize) < self.nodes.len() {
+            for word in words {
+                outbox.send(destination as usize, word);
+            }
+        } else {
+            self.external.push(Packet { source, destination, words });
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Network {
+    scheduler: Scheduler,
+    routes: Routes
+}
+
+impl Network {
+    /// An empty network where outputs go to linked nodes, see `connect`.
+    pub fn new() -> Self {
+        Network::default()
+    }
+
+    /// A network where node `i` runs `program` with `initial_inputs[i]` queued and sends its
+    /// outputs to node `i + 1`.
+    pub fn chain(program: &[i64], initial_inputs: &[Vec<i64>]) -> Self {
+        let mut network = Network::new();
+        for inputs in initial_inputs {
+            network.add_node(Machine::new(program), inputs);
+        }
+        for i in 1..network.len() {
+            network.connect(i - 1, i);
+        }
+        network
+    }
+
+    /// A chain whose last node feeds back into the first, like day7 part 2's feedback loop.
+    pub fn ring(program: &[i64], initial_inputs: &[Vec<i64>]) -> Self {
+        let mut network = Network::chain(program, initial_inputs);
+        if network.len() > 1 {
+            network.connect(network.len() - 1, 0);
+        }
+        network
+    }
+
+    /// Node 0 sends its outputs to every other node and they all send theirs back to node 0.
+    pub fn star(program: &[i64], initial_inputs: &[Vec<i64>]) -> Self {
+        let mut network = Network::new();
+        for inputs in initial_inputs {
+            network.add_node(Machine::new(program), inputs);
+        }
+        for i in 1..network.len() {
+            network.connect(0, i);
+            network.connect(i, 0);
+        }
+        network
+    }
+
+    /// `nodes` copies of `program` that exchange packets.
+    ///
+    /// Every node gets its address as the first input. A packet is `packet_size + 1` outputs,
+    /// the destination address followed by the words it delivers. Packets to addresses outside of
+    /// the network are kept, see `external_packets`. With an `idle_input` a node waiting for a
+    /// packet reads that value instead of blocking, like day 23's `-1`.
+    pub fn addressed(program: &[i64], nodes: usize, packet_size: usize, idle_input: Option<i64>) -> Self {
+        let mut network = Network::new();
+        for address in 0..nodes {
+            network.add_node(Machine::new(program), &[address as i64]);
+        }
+        network.routes.packet_size = Some(packet_size);
+        network.scheduler.set_idle_input(idle_input);
+        network
+    }
+
+    /// Adds a node and returns its id. Ids are handed out in order starting at 0.
+    pub fn add_node(&mut self, mut machine: Machine, initial_inputs: &[i64]) -> NodeId {
+        for value in initial_inputs {
+            machine.push_input(*value);
+        }
+        self.routes.nodes.push(Node::default());
+        self.scheduler.spawn(machine)
+    }
+
+    /// Sends every output of `from` to `to` as well. Ignored in addressed mode.
+    pub fn connect(&mut self, from: NodeId, to: NodeId) {
+        self.routes.nodes[from].links.push(to);
+    }
+
+    pub fn push_input(&mut self, node: NodeId, value: i64) {
+        self.scheduler.send(node, value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.nodes.is_empty()
+    }
+
+    pub fn machine(&self, node: NodeId) -> &Machine {
+        self.scheduler.machine(node)
+    }
+
+    /// Everything `node` output so far, wherever it was sent.
+    pub fn outputs(&self, node: NodeId) -> &[i64] {
+        &self.routes.nodes[node].outputs
+    }
+
+    pub fn halted(&self, node: NodeId) -> bool {
+        self.scheduler.state(node) == TaskState::Halted
+    }
+
+    pub fn external_packets(&self) -> &[Packet] {
+        &self.routes.external
+    }
+
+    /// Runs the nodes on a `Scheduler` until every node halted.
+    ///
+    /// Fails on the first program error, or with `Deadlock` when no node can make progress, see
+    /// `RunOutcome::Idle`. Outputs stay available after an error.
+    pub fn run(&mut self) -> Result<(), NetworkError> {
+        let routes = &mut self.routes;
+        let outcome = self.scheduler.run(|source, value, outbox| routes.route(source, value, outbox))
+            .map_err(|e| NetworkError::Vm { node: e.task, error: e.error })?;
+        match outcome {
+            // the faulted nodes' errors were returned by earlier runs
+            RunOutcome::AllHalted | RunOutcome::AllDone => Ok(()),
+            RunOutcome::Idle { blocked } => Err(NetworkError::Deadlock { blocked })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::{Network, NetworkError, Packet};
+    use crate::parse_program;
+
+    #[test]
+    fn day7_chain_and_ring() {
+        let program = parse_program("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+        let phases: Vec<Vec<i64>> = [4, 3, 2, 1, 0].iter().map(|p| vec![*p]).collect();
+        let mut amplifiers = Network::chain(&program, &phases);
+        amplifiers.push_input(0, 0);
+        amplifiers.run().unwrap();
+        assert_eq!(amplifiers.outputs(4), &[43210]);
+
+        let program = parse_program("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5");
+        let phases: Vec<Vec<i64>> = [9, 8, 7, 6, 5].iter().map(|p| vec![*p]).collect();
+        let mut amplifiers = Network::ring(&program, &phases);
+        amplifiers.push_input(0, 0);
+        amplifiers.run().unwrap();
+        assert_eq!(amplifiers.outputs(4).last(), Some(&139629729));
+    }
+
+    #[test]
+    fn deadlock() {
+        // both nodes wait for the other one's output
+        let mut network = Network::ring(&parse_program("3,0,4,0,99"), &[vec![], vec![]]);
+        assert_eq!(network.run(), Err(NetworkError::Deadlock { blocked: vec![0, 1] }));
+
+        network.push_input(1, 7);
+        assert_eq!(network.run(), Ok(()));
+        assert_eq!(network.outputs(0), &[7]);
+    }
+
+    #[test]
+    fn addressed_packets() {
+        // node 0 sends 42 to node 1, node 1 forwards what it gets to address 255
+        let program = parse_program("3,100,1005,100,10,104,1,104,42,99,3,101,104,255,4,101,99");
+        let mut network = Network::addressed(&program, 2, 1, Some(-1));
+        network.run().unwrap();
+        assert_eq!(network.external_packets(), &[Packet { source: 1, destination: 255, words: vec![42] }]);
+    }
+}
@@ -0,0 +1,426 @@
+//! Searching memory patches for a wanted result, see day2 part 2.
+//!
+//! A program is run with some of its cells overwritten and the value left in an output cell is
+//! checked. Searching tries every combination of patch values on several threads. Many programs,
+//! like day2's, compute an affine function of the patched cells, which `PatchSearch::affine`
+//! detects so `PatchSearch::solve` can work out the values instead.
+
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use crate::isa::IsaProfile;
+use crate::machine::{Machine, StopReason};
+use crate::snapshot::Snapshot;
+
+/// A cell to overwrite before running and the values to try for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub address: i64,
+    pub values: RangeInclusive<i64>
+}
+
+impl Patch {
+    pub fn new(address: i64, values: RangeInclusive<i64>) -> Self {
+        Patch { address, values }
+    }
+
+    fn len(&self) -> usize {
+        if self.values.is_empty() {
+            0
+        } else {
+            (*self.values.end() as i128 - *self.values.start() as i128 + 1).min(usize::MAX as i128) as usize
+        }
+    }
+}
+
+/// Patch values, in the order of the patches, and the output they lead to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchMatch {
+    pub values: Vec<i64>,
+    pub output: i64
+}
+
+/// An output of `constant + coefficients[0] * values[0] + coefficients[1] * values[1] + ...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Affine {
+    pub constant: i128,
+    pub coefficients: Vec<i128>
+}
+
+impl Affine {
+    /// The output for `values`, `None` if it doesn't fit an `i128`.
+    pub fn output(&self, values: &[i64]) -> Option<i128> {
+        self.coefficients.iter().zip(values)
+            .try_fold(self.constant, |sum, (c, v)| c.checked_mul(*v as i128).and_then(|term| sum.checked_add(term)))
+    }
+}
+
+/// How many combinations a worker takes at once.
+const BATCH_SIZE: usize = 64;
+
+/// Random points besides the corners `affine` checks its guess on.
+const AFFINE_SAMPLES: usize = 16;
+
+/// Instructions a run may take before it counts as stuck, see `PatchSearch::set_step_limit`.
+pub const DEFAULT_PATCH_STEP_LIMIT: u64 = 1_000_000;
+
+#[derive(Debug, Clone)]
+pub struct PatchSearch {
+    start: Snapshot,
+    output_address: i64,
+    patches: Vec<Patch>,
+    step_limit: u64,
+    profile: IsaProfile
+}
+
+impl PatchSearch {
+    /// Searches `patches` of `program`, reading the result from `output_address` once it halted.
+    pub fn new(program: &[i64], output_address: i64, patches: Vec<Patch>) -> Self {
+        PatchSearch { start: Machine::new(program).snapshot(), output_address, patches, step_limit: DEFAULT_PATCH_STEP_LIMIT, profile: IsaProfile::default() }
+    }
+
+    /// Makes runs taking more than `limit` instructions fail, so patches that send the program
+    /// into a loop don't match. `DEFAULT_PATCH_STEP_LIMIT` unless set.
+    pub fn set_step_limit(&mut self, limit: u64) {
+        self.step_limit = limit;
+    }
+
+    /// Runs the program with `Machine::set_profile(profile)`.
+    pub fn set_profile(&mut self, profile: IsaProfile) {
+        self.profile = profile;
+    }
+
+    /// Number of value combinations.
+    pub fn len(&self) -> usize {
+        self.patches.iter().fold(1usize, |n, p| n.saturating_mul(p.len()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Runs the program with `values` patched in, on `m` to save allocating a machine.
+    fn run_on(&self, m: &mut Machine, values: &[i64]) -> Option<i64> {
+        m.restore(&self.start);
+        m.set_step_limit(Some(self.step_limit));
+        m.set_profile(self.profile);
+        for (patch, value) in self.patches.iter().zip(values) {
+            if patch.address < 0 || patch.address as usize >= m.memory().limit() {
+                return None;
+            }
+            m.write(patch.address, *value);
+        }
+        loop {
+            match m.run() {
+                Ok(StopReason::Halted) => return m.memory().get(self.output_address).ok(),
+                Ok(StopReason::Output(_)) => {},
+                Ok(StopReason::NeedInput) | Err(_) => return None
+            }
+        }
+    }
+
+    /// The output with `values` patched in, `None` if the program fails, runs into the step limit
+    /// or waits for input.
+    ///
+    /// The program gets no inputs and its outputs are ignored.
+    pub fn run(&self, values: &[i64]) -> Option<i64> {
+        self.run_on(&mut Machine::new(&[]), values)
+    }
+
+    /// The `index`th combination, the last patch changing fastest.
+    fn values_at(&self, mut index: usize) -> Vec<i64> {
+        let mut values = vec![0; self.patches.len()];
+        for (value, patch) in values.iter_mut().zip(&self.patches).rev() {
+            let len = patch.len();
+            *value = (*patch.values.start() as i128 + (index % len) as i128) as i64;
+            index /= len;
+        }
+        values
+    }
+
+    /// The first combination, the last patch changing fastest, whose output satisfies `predicate`.
+    ///
+    /// Combinations are spread over `threads` threads, 0 meaning one per CPU. Combinations the
+    /// program fails on don't match.
+    pub fn search<P>(&self, threads: usize, predicate: P) -> Option<PatchMatch>
+        where P: Fn(i64) -> bool + Sync
+    {
+        let threads = if threads == 0 {
+            thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+        } else {
+            threads
+        };
+        let total = self.len();
+        let next = AtomicUsize::new(0);
+        let first_match = AtomicUsize::new(usize::MAX);
+
+        let worker = || {
+            let mut m = Machine::new(&[]);
+            let mut found = None;
+            loop {
+                let start = next.fetch_add(BATCH_SIZE, Ordering::Relaxed);
+                if start >= total || start > first_match.load(Ordering::Relaxed) {
+                    return found;
+                }
+                for index in start..total.min(start.saturating_add(BATCH_SIZE)) {
+                    let values = self.values_at(index);
+                    match self.run_on(&mut m, &values) {
+                        Some(output) if predicate(output) => {
+                            first_match.fetch_min(index, Ordering::Relaxed);
+                            found = Some((index, PatchMatch { values, output }));
+                            break;
+                        },
+                        _ => {}
+                    }
+                }
+            }
+        };
+
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads).map(|_| scope.spawn(worker)).collect();
+            workers.into_iter()
+                .filter_map(|w| w.join().unwrap())
+                .min_by_key(|(index, _)| *index)
+                .map(|(_, found)| found)
+        })
+    }
+
+    /// The affine function the output is of the patch values, if it looks like there is one.
+    ///
+    /// The coefficients are measured by changing one value at a time from the smallest ones and
+    /// the guess is checked on the largest values, every pair of changed values and a sample of
+    /// random points. That can be fooled by a program that only differs somewhere else.
+    pub fn affine(&self) -> Option<Affine> {
+        let base: Vec<i64> = self.patches.iter().map(|p| *p.values.start()).collect();
+        if self.is_empty() {
+            return None;
+        }
+        let mut m = Machine::new(&[]);
+        let base_output = self.run_on(&mut m, &base)? as i128;
+
+        let mut coefficients = Vec::new();
+        for (i, patch) in self.patches.iter().enumerate() {
+            if patch.len() < 2 {
+                coefficients.push(0);
+                continue;
+            }
+            let mut values = base.clone();
+            values[i] += 1;
+            coefficients.push(self.run_on(&mut m, &values)? as i128 - base_output);
+        }
+        let constant = coefficients.iter().zip(&base)
+            .try_fold(base_output, |sum, (c, v)| c.checked_mul(*v as i128).and_then(|term| sum.checked_sub(term)))?;
+        let affine = Affine { constant, coefficients };
+
+        let mut points = vec![self.patches.iter().map(|p| *p.values.end()).collect::<Vec<_>>()];
+        for i in 0..self.patches.len() {
+            for j in i + 1..self.patches.len() {
+                let mut values = base.clone();
+                values[i] = *self.patches[i].values.end();
+                values[j] = *self.patches[j].values.end();
+                points.push(values);
+            }
+        }
+        // xorshift, the points only need to be spread out
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..AFFINE_SAMPLES {
+            let values = self.patches.iter().map(|p| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (*p.values.start() as i128 + (state % p.len() as u64) as i128) as i64
+            }).collect();
+            points.push(values);
+        }
+
+        for values in points {
+            if Some(self.run_on(&mut m, &values)? as i128) != affine.output(&values) {
+                return None;
+            }
+        }
+        Some(affine)
+    }
+
+    /// Patch values that make the output `target`.
+    ///
+    /// When the output is affine they are worked out directly, which isn't necessarily the first
+    /// match `search` would find. Otherwise, or if there are no such values or they don't check
+    /// out, which happens when `affine` was fooled, this searches with `threads` threads.
+    pub fn solve(&self, target: i64, threads: usize) -> Option<PatchMatch> {
+        if let Some(affine) = self.affine() {
+            let mut order: Vec<usize> = (0..self.patches.len()).collect();
+            order.sort_by_key(|i| std::cmp::Reverse(affine.coefficients[*i].abs()));
+            let mut values: Vec<i64> = self.patches.iter().map(|p| *p.values.start()).collect();
+            let solved = (target as i128).checked_sub(affine.constant)
+                .is_some_and(|rest| solve_affine(&affine, &self.patches, &order, rest, &mut values));
+            if solved && self.run(&values) == Some(target) {
+                return Some(PatchMatch { values, output: target });
+            }
+        }
+        self.search(threads, |output| output == target)
+    }
+}
+
+/// Picks values for the patches in `order` so that their part of the sum is `rest`.
+///
+/// Patches are taken largest coefficient first and each only tries the values the patches after
+/// it could still make up for, so this rarely has to backtrack. Sums the coefficients can't add up
+/// to, because `rest` isn't a multiple of their greatest common divisor, are rejected right away.
+/// Anything that overflows an `i128` counts as no solution.
+fn solve_affine(affine: &Affine, patches: &[Patch], order: &[usize], rest: i128, values: &mut [i64]) -> bool {
+    let divisor = order.iter().fold(0, |g, i| gcd(g, affine.coefficients[*i].unsigned_abs()));
+    if !rest.unsigned_abs().is_multiple_of(divisor) {
+        return false;
+    }
+    let (i, later) = match order.split_first() {
+        Some(split) => split,
+        None => return rest == 0
+    };
+    let (i, c) = (*i, affine.coefficients[*i]);
+    let (low, high) = (*patches[i].values.start() as i128, *patches[i].values.end() as i128);
+    if c == 0 {
+        values[i] = low as i64;
+        return solve_affine(affine, patches, later, rest, values);
+    }
+
+    let (from, to) = match value_range(affine, patches, later, rest, c) {
+        Some(range) => range,
+        None => return false
+    };
+    for value in from.max(low)..=to.min(high) {
+        values[i] = value as i64;
+        let later_rest = match c.checked_mul(value).and_then(|part| rest.checked_sub(part)) {
+            Some(later_rest) => later_rest,
+            None => continue
+        };
+        if solve_affine(affine, patches, later, later_rest, values) {
+            return true;
+        }
+    }
+    false
+}
+
+/// The values with coefficient `c` that leave a `rest` the `later` patches can still make up.
+fn value_range(affine: &Affine, patches: &[Patch], later: &[usize], rest: i128, c: i128) -> Option<(i128, i128)> {
+    // the range of sums the later patches can make
+    let (mut least, mut most) = (0i128, 0i128);
+    for j in later {
+        let c = affine.coefficients[*j];
+        let ends = [c.checked_mul(*patches[*j].values.start() as i128)?, c.checked_mul(*patches[*j].values.end() as i128)?];
+        least = least.checked_add(ends[0].min(ends[1]))?;
+        most = most.checked_add(ends[0].max(ends[1]))?;
+    }
+    // c * value has to be in rest - most ..= rest - least
+    let (low, high) = (rest.checked_sub(most)?, rest.checked_sub(least)?);
+    if c > 0 {
+        Some((div_ceil(low, c)?, div_floor(high, c)?))
+    } else {
+        Some((div_ceil(high, c)?, div_floor(low, c)?))
+    }
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn div_floor(a: i128, b: i128) -> Option<i128> {
+    let q = a.checked_div(b)?;
+    if (a % b != 0) && ((a < 0) != (b < 0)) { Some(q - 1) } else { Some(q) }
+}
+
+fn div_ceil(a: i128, b: i128) -> Option<i128> {
+    let q = a.checked_div(b)?;
+    if (a % b != 0) && ((a < 0) == (b < 0)) { Some(q + 1) } else { Some(q) }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patch::{solve_affine, Affine, Patch, PatchMatch, PatchSearch};
+    use crate::{parse_program, IsaProfile};
+
+    #[test]
+    fn affine_output_is_solved() {
+        // [0] = 100 * [1] + [6]
+        let program = parse_program("1102,0,100,13,1001,13,0,0,99");
+        let search = PatchSearch::new(&program, 0, vec![Patch::new(1, 0..=99), Patch::new(6, 0..=99)]);
+        assert_eq!(search.affine(), Some(Affine { constant: 0, coefficients: vec![100, 1] }));
+
+        let expected = Some(PatchMatch { values: vec![42, 17], output: 4217 });
+        assert_eq!(search.solve(4217, 0), expected);
+        assert_eq!(search.search(3, |output| output == 4217), expected);
+        assert_eq!(search.solve(10000, 0), None);
+    }
+
+    #[test]
+    fn wrong_affine_guesses_are_searched() {
+        // [0] = 100 * [2] + [6], except for 42 and 17, which give 100000
+        let program = parse_program("1102,100,0,30,1001,30,0,30,1008,30,4217,31,1006,31,19,1101,0,100000,30,1001,30,0,0,99");
+        let search = PatchSearch::new(&program, 0, vec![Patch::new(2, 0..=99), Patch::new(6, 0..=99)]);
+        assert_eq!(search.affine(), Some(Affine { constant: 0, coefficients: vec![100, 1] }));
+        assert_eq!(search.solve(100000, 2), Some(PatchMatch { values: vec![42, 17], output: 100000 }));
+        // the values worked out for 4217 don't give it and nothing else does
+        assert_eq!(search.solve(4217, 2), None);
+    }
+
+    #[test]
+    fn other_outputs_are_searched() {
+        // [0] = [1] * [2]
+        let search = PatchSearch::new(&parse_program("1102,0,0,0,99"), 0, vec![Patch::new(1, 0..=9), Patch::new(2, 0..=9)]);
+        assert_eq!(search.affine(), None);
+        for threads in 1..=4 {
+            assert_eq!(search.solve(12, threads), Some(PatchMatch { values: vec![2, 6], output: 12 }));
+        }
+        assert_eq!(search.search(0, |output| output > 80), Some(PatchMatch { values: vec![9, 9], output: 81 }));
+        assert_eq!(search.solve(13, 2), None);
+    }
+
+    #[test]
+    fn failing_runs_dont_match() {
+        // opcode 0 doesn't exist, 1 doubles [0]
+        let search = PatchSearch::new(&parse_program("0,0,0,0,99"), 0, vec![Patch::new(0, 0..=2)]);
+        assert_eq!(search.run(&[0]), None);
+        assert_eq!(search.search(2, |_| true), Some(PatchMatch { values: vec![1], output: 2 }));
+    }
+
+    #[test]
+    fn looping_runs_dont_match() {
+        // JNZ #1, #[2], jumping back to 0 loops forever and jumping to 3 halts
+        let mut search = PatchSearch::new(&parse_program("1105,1,3,99"), 0, vec![Patch::new(2, 0..=3)]);
+        search.set_step_limit(1000);
+        assert_eq!(search.run(&[0]), None);
+        assert_eq!(search.search(2, |_| true), Some(PatchMatch { values: vec![3], output: 1105 }));
+        assert_eq!(search.affine(), None);
+    }
+
+    #[test]
+    fn profile() {
+        // [0] = [1] + [2] in immediate mode, which day 2 doesn't have
+        let mut search = PatchSearch::new(&parse_program("1101,0,0,0,99"), 0, vec![Patch::new(1, 0..=9), Patch::new(2, 0..=9)]);
+        assert_eq!(search.run(&[2, 3]), Some(5));
+        search.set_profile(IsaProfile::Day2);
+        assert_eq!(search.run(&[2, 3]), None);
+    }
+
+    #[test]
+    fn solve_affine_rejects_early() {
+        let patches = vec![Patch::new(0, 0..=i64::MAX / 4), Patch::new(1, 0..=i64::MAX / 4)];
+        let mut values = vec![0, 0];
+
+        // 2 * a + 2 * b is never odd, walking the values to find that out would take forever
+        let even = Affine { constant: 0, coefficients: vec![2, 2] };
+        assert!(!solve_affine(&even, &patches, &[0, 1], 1_000_000_001, &mut values));
+        assert!(solve_affine(&even, &patches, &[0, 1], 1_000_000_002, &mut values));
+        assert_eq!(even.output(&values), Some(1_000_000_002));
+
+        // the sums the second patch can make don't fit an i128
+        let huge = Affine { constant: 0, coefficients: vec![3, i128::MAX / 2] };
+        assert!(!solve_affine(&huge, &patches, &[0, 1], 1, &mut values));
+    }
+
+    #[test]
+    fn affine_output_overflow() {
+        let affine = Affine { constant: 1, coefficients: vec![i128::MAX / 2] };
+        assert_eq!(affine.output(&[1]), Some(i128::MAX / 2 + 1));
+        assert_eq!(affine.output(&[3]), None);
+    }
+}
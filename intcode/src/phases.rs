@@ -0,0 +1,162 @@
+//! Searching amplifier phase settings, see day7.
+
+use std::sync::Mutex;
+use std::thread;
+
+/// Ordered selections of `k` items without repetition, in lexicographic order of the items'
+/// positions. With `k` equal to the number of items these are all the permutations.
+#[derive(Debug, Clone)]
+pub struct Permutations<T> {
+    items: Vec<T>,
+    indices: Vec<usize>,
+    k: usize,
+    done: bool
+}
+
+impl<T: Clone> Permutations<T> {
+    pub fn new(items: &[T], k: usize) -> Self {
+        Permutations {
+            items: items.to_vec(),
+            indices: (0..items.len()).collect(),
+            k,
+            done: k > items.len()
+        }
+    }
+}
+
+/// Rearranges `a` into the next greater permutation, returns false if it was the last one.
+fn next_permutation(a: &mut [usize]) -> bool {
+    if a.len() < 2 {
+        return false;
+    }
+
+    let mut i = a.len() - 1;
+    while i > 0 && a[i - 1] >= a[i] {
+        i -= 1;
+    }
+    if i == 0 {
+        return false;
+    }
+
+    let mut j = a.len() - 1;
+    while a[j] <= a[i - 1] {
+        j -= 1;
+    }
+    a.swap(i - 1, j);
+    a[i..].reverse();
+    true
+}
+
+impl<T: Clone> Iterator for Permutations<T> {
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Vec<T>> {
+        if self.done {
+            return None;
+        }
+
+        let permutation = self.indices[..self.k].iter().map(|i| self.items[*i].clone()).collect();
+        // the unused tail is kept ascending, reversing it skips every ordering of it
+        self.indices[self.k..].reverse();
+        self.done = !next_permutation(&mut self.indices);
+        Some(permutation)
+    }
+}
+
+/// The best phase setting ordering found by `search_phases`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhaseSearch {
+    pub phases: Vec<i64>,
+    pub thrust: i64
+}
+
+/// How many orderings a worker takes off the shared iterator at once.
+const BATCH_SIZE: usize = 16;
+
+/// Tries every ordering of `amplifiers` distinct values out of `phase_values` and returns the one
+/// `thrust` rates highest, or `None` if there are fewer values than amplifiers.
+///
+/// Orderings are spread over `threads` threads, 0 meaning one per CPU. Ties go to the ordering
+/// that comes first, so the result doesn't depend on the thread count.
+pub fn search_phases<F>(phase_values: &[i64], amplifiers: usize, threads: usize, thrust: F) -> Option<PhaseSearch>
+    where F: Fn(&[i64]) -> i64 + Sync
+{
+    let threads = if threads == 0 {
+        thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    } else {
+        threads
+    };
+    let orderings = Mutex::new(Permutations::new(phase_values, amplifiers).enumerate());
+
+    let worker = || {
+        let mut best: Option<(usize, PhaseSearch)> = None;
+        loop {
+            let batch: Vec<(usize, Vec<i64>)> = orderings.lock().unwrap().by_ref().take(BATCH_SIZE).collect();
+            if batch.is_empty() {
+                return best;
+            }
+            for (index, phases) in batch {
+                let candidate = (index, PhaseSearch { thrust: thrust(&phases), phases });
+                best = better(best, candidate);
+            }
+        }
+    };
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads).map(|_| scope.spawn(worker)).collect();
+        workers.into_iter()
+            .filter_map(|w| w.join().unwrap())
+            .fold(None, better)
+            .map(|(_, best)| best)
+    })
+}
+
+fn better(best: Option<(usize, PhaseSearch)>, candidate: (usize, PhaseSearch)) -> Option<(usize, PhaseSearch)> {
+    match best {
+        Some(best) if (best.1.thrust, candidate.0) >= (candidate.1.thrust, best.0) => Some(best),
+        _ => Some(candidate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::phases::{search_phases, PhaseSearch, Permutations};
+    use crate::{parse_program, Network};
+
+    #[test]
+    fn permutations() {
+        assert_eq!(Permutations::new(&[1, 2, 3], 3).collect::<Vec<_>>(), vec![
+            vec![1, 2, 3], vec![1, 3, 2], vec![2, 1, 3], vec![2, 3, 1], vec![3, 1, 2], vec![3, 2, 1]
+        ]);
+        assert_eq!(Permutations::new(&[1, 2, 3], 2).collect::<Vec<_>>(), vec![
+            vec![1, 2], vec![1, 3], vec![2, 1], vec![2, 3], vec![3, 1], vec![3, 2]
+        ]);
+        assert_eq!(Permutations::new(&[0, 1, 2, 3, 4, 5, 6], 7).count(), 5040);
+        assert_eq!(Permutations::new(&[1, 2], 0).count(), 1);
+        assert_eq!(Permutations::new(&[1, 2], 3).count(), 0);
+    }
+
+    #[test]
+    fn day7_examples() {
+        let program = parse_program("3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0");
+        let thrust = |phases: &[i64]| {
+            let phases: Vec<Vec<i64>> = phases.iter().map(|p| vec![*p]).collect();
+            let mut amplifiers = Network::chain(&program, &phases);
+            amplifiers.push_input(0, 0);
+            amplifiers.run().unwrap();
+            amplifiers.outputs(phases.len() - 1)[0]
+        };
+
+        for threads in 1..=4 {
+            let best = search_phases(&[0, 1, 2, 3, 4], 5, threads, thrust);
+            assert_eq!(best, Some(PhaseSearch { phases: vec![1, 0, 4, 3, 2], thrust: 65210 }));
+        }
+        assert_eq!(search_phases(&[0, 1], 3, 0, thrust), None);
+    }
+
+    #[test]
+    fn ties_go_to_the_first_ordering() {
+        let best = search_phases(&[0, 1, 2, 3], 2, 3, |phases| phases[0]);
+        assert_eq!(best, Some(PhaseSearch { phases: vec![3, 0], thrust: 3 }));
+    }
+}
@@ -0,0 +1,282 @@
+//! Runs many machines on one thread.
+//!
+//! A task runs until it needs input it doesn't have or halts, then the next ready task runs.
+//! Tasks are picked in the order they became ready, so a run is deterministic.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use crate::error::VmError;
+use crate::machine::{Machine, StopReason};
+
+pub type TaskId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    /// Waiting in the ready queue.
+    Ready,
+    /// Needs input nobody has sent yet.
+    Blocked,
+    Halted,
+    /// Failed with the `TaskError` a `run` returned, it never runs again.
+    Faulted
+}
+
+/// Why `Scheduler::run` returned without an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunOutcome {
+    AllHalted,
+    /// Every task halted or faulted, and at least one faulted in an earlier `run`.
+    AllDone,
+    /// No task can make progress: every task that hasn't halted or faulted is blocked, or with an
+    /// idle input it read the idle value and asked again without sending anything. `blocked` is
+    /// never empty.
+    Idle { blocked: Vec<TaskId> }
+}
+
+/// A program error in one of the tasks. That task is left `Faulted`, the others as they were.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskError {
+    pub task: TaskId,
+    pub error: VmError
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task {}: {}", self.task, self.error)
+    }
+}
+
+impl Error for TaskError {}
+
+/// Values an output handler wants delivered, see `Scheduler::run`.
+#[derive(Debug, Default)]
+pub struct Outbox {
+    messages: Vec<(TaskId, i64)>
+}
+
+impl Outbox {
+    pub fn send(&mut self, task: TaskId, value: i64) {
+        self.messages.push((task, value));
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Task {
+    machine: Machine,
+    state: TaskState,
+    /// Got the idle input and hasn't sent or received anything since.
+    fed_idle: bool,
+    /// Asked for input again after `fed_idle`.
+    idle: bool
+}
+
+impl Task {
+    /// Halted or faulted, so it won't run again.
+    fn is_done(&self) -> bool {
+        matches!(self.state, TaskState::Halted | TaskState::Faulted)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler {
+    tasks: Vec<Task>,
+    ready: VecDeque<TaskId>,
+    idle_input: Option<i64>
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler::default()
+    }
+
+    /// Tasks that need input get `value` instead of blocking, like day 23's `-1`.
+    pub fn set_idle_input(&mut self, value: Option<i64>) {
+        self.idle_input = value;
+    }
+
+    /// Adds a ready task and returns its id. Ids are handed out in order starting at 0.
+    pub fn spawn(&mut self, machine: Machine) -> TaskId {
+        self.tasks.push(Task { machine, state: TaskState::Ready, fed_idle: false, idle: false });
+        self.ready.push_back(self.tasks.len() - 1);
+        self.tasks.len() - 1
+    }
+
+    /// Queues an input for `task`, waking it up if it was blocked.
+    pub fn send(&mut self, task: TaskId, value: i64) {
+        let t = &mut self.tasks[task];
+        t.machine.push_input(value);
+        t.fed_idle = false;
+        t.idle = false;
+        if t.state == TaskState::Blocked {
+            t.state = TaskState::Ready;
+            self.ready.push_back(task);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub fn state(&self, task: TaskId) -> TaskState {
+        self.tasks[task].state
+    }
+
+    pub fn machine(&self, task: TaskId) -> &Machine {
+        &self.tasks[task].machine
+    }
+
+    fn blocked(&self) -> Vec<TaskId> {
+        (0..self.tasks.len()).filter(|t| !self.tasks[*t].is_done()).collect()
+    }
+
+    /// Runs tasks until every one halted or none can make progress.
+    ///
+    /// Every output is handed to `on_output` with the task that produced it. Whatever the handler
+    /// puts into the outbox is delivered as if by `send` before the next output is handled.
+    pub fn run<F>(&mut self, mut on_output: F) -> Result<RunOutcome, TaskError>
+        where F: FnMut(TaskId, i64, &mut Outbox)
+    {
+        let mut outbox = Outbox::default();
+        while let Some(id) = self.ready.pop_front() {
+            loop {
+                let result = match self.tasks[id].machine.run() {
+                    Ok(result) => result,
+                    Err(error) => {
+                        self.tasks[id].state = TaskState::Faulted;
+                        return Err(TaskError { task: id, error });
+                    }
+                };
+                match result {
+                    StopReason::Output(value) => {
+                        self.tasks[id].fed_idle = false;
+                        self.tasks[id].idle = false;
+                        on_output(id, value, &mut outbox);
+                        for (to, value) in outbox.messages.drain(..) {
+                            self.send(to, value);
+                        }
+                    },
+                    StopReason::Halted => {
+                        self.tasks[id].state = TaskState::Halted;
+                        break;
+                    },
+                    StopReason::NeedInput => {
+                        let idle_input = match self.idle_input {
+                            Some(value) => value,
+                            None => {
+                                self.tasks[id].state = TaskState::Blocked;
+                                break;
+                            }
+                        };
+
+                        let task = &mut self.tasks[id];
+                        task.idle = task.fed_idle;
+                        if self.tasks.iter().all(|t| t.idle || t.is_done()) {
+                            // left ready, a later run carries on where this one stopped
+                            self.ready.push_front(id);
+                            return Ok(RunOutcome::Idle { blocked: self.blocked() });
+                        }
+
+                        let task = &mut self.tasks[id];
+                        task.machine.push_input(idle_input);
+                        task.fed_idle = true;
+                        self.ready.push_back(id);
+                        break;
+                    }
+                }
+            }
+        }
+
+        if self.tasks.iter().all(|t| t.state == TaskState::Halted) {
+            Ok(RunOutcome::AllHalted)
+        } else if self.tasks.iter().all(|t| t.is_done()) {
+            Ok(RunOutcome::AllDone)
+        } else {
+            Ok(RunOutcome::Idle { blocked: self.blocked() })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scheduler::{RunOutcome, Scheduler, TaskState};
+    use crate::{parse_program, Machine};
+
+    #[test]
+    fn hundreds_of_machines_in_a_ring() {
+        // every machine adds one to what it's sent and passes it on, 300 times round
+        let program = parse_program("3,20,1001,20,1,20,4,20,1105,1,0");
+        let mut scheduler = Scheduler::new();
+        for _ in 0..300 {
+            scheduler.spawn(Machine::new(&program));
+        }
+        scheduler.send(0, 0);
+
+        let mut last = 0;
+        let outcome = scheduler.run(|from, value, outbox| {
+            last = value;
+            if value < 300 * 300 {
+                outbox.send((from + 1) % 300, value);
+            }
+        });
+        assert_eq!(outcome, Ok(RunOutcome::Idle { blocked: (0..300).collect() }));
+        assert_eq!(last, 300 * 300);
+        assert_eq!(scheduler.state(7), TaskState::Blocked);
+    }
+
+    #[test]
+    fn idle_input() {
+        // counts how often it read the idle value and reports when it gets a real input
+        let program = parse_program("3,100,1008,100,-1,101,1006,101,16,1001,102,1,102,1105,1,0,4,102,99");
+        let mut scheduler = Scheduler::new();
+        scheduler.set_idle_input(Some(-1));
+        scheduler.spawn(Machine::new(&program));
+        scheduler.spawn(Machine::new(&program));
+
+        let outcome = scheduler.run(|_, _, _| {});
+        assert_eq!(outcome, Ok(RunOutcome::Idle { blocked: vec![0, 1] }));
+
+        scheduler.send(1, 5);
+        let mut outputs = Vec::new();
+        let outcome = scheduler.run(|from, value, _| outputs.push((from, value)));
+        assert_eq!(outcome, Ok(RunOutcome::Idle { blocked: vec![0] }));
+        assert_eq!(outputs, vec![(1, 1)]);
+        assert_eq!(scheduler.state(1), TaskState::Halted);
+    }
+
+    #[test]
+    fn errors_name_the_task() {
+        let mut scheduler = Scheduler::new();
+        scheduler.spawn(Machine::new(&[99]));
+        scheduler.spawn(Machine::new(&[42]));
+        let error = scheduler.run(|_, _, _| {}).unwrap_err();
+        assert_eq!(error.task, 1);
+        assert_eq!(scheduler.state(0), TaskState::Halted);
+        assert_eq!(scheduler.state(1), TaskState::Faulted);
+    }
+
+    #[test]
+    fn run_after_an_error() {
+        // task 1 faults before task 2 gets to run, a second run carries on with task 2
+        let mut scheduler = Scheduler::new();
+        scheduler.spawn(Machine::new(&parse_program("3,10,4,10,99")));
+        scheduler.spawn(Machine::new(&[42]));
+        scheduler.spawn(Machine::new(&parse_program("104,7,99")));
+        assert_eq!(scheduler.run(|_, _, _| {}).unwrap_err().task, 1);
+
+        let mut outputs = Vec::new();
+        assert_eq!(scheduler.run(|from, value, _| outputs.push((from, value))), Ok(RunOutcome::Idle { blocked: vec![0] }));
+        assert_eq!(outputs, vec![(2, 7)]);
+        assert_eq!(scheduler.state(1), TaskState::Faulted);
+
+        scheduler.send(1, 3);
+        scheduler.send(0, 5);
+        let outcome = scheduler.run(|from, value, _| outputs.push((from, value)));
+        assert_eq!(outcome, Ok(RunOutcome::AllDone));
+        assert_eq!(outputs, vec![(2, 7), (0, 5)]);
+    }
+}
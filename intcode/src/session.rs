@@ -0,0 +1,336 @@
+//! Recording interactive runs and replaying them, to catch interpreter regressions without the
+//! controller that drove the run, like day 13's joystick logic.
+//!
+//! A `Recorder` wraps a `Machine` like `AsciiIo` does and logs every input the program reads and
+//! every output it produces, with the number of instructions executed up to then. The resulting
+//! `Session` starts with a snapshot of the machine, so it can be replayed on its own:
+//!
+//! ```text
+//! intcode-session 1
+//! intcode-snapshot 1
+//! ip 0
+//! rb 0
+//! limit 16777216
+//! inputs
+//! memory 3,9,4,9,1105,1,0,99,0,0
+//! 1 in 5
+//! 2 out 5
+//! 4 in 0
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use crate::decode::Opcode;
+use crate::error::VmError;
+use crate::machine::{Machine, StopReason};
+use crate::snapshot::{Snapshot, SnapshotError};
+use crate::trace::TraceStep;
+
+const HEADER: &str = "intcode-session 1";
+
+/// Lines of the snapshot at the start of a session file.
+const SNAPSHOT_LINES: usize = 6;
+
+/// Something the program did, after executing `steps` instructions in total, that instruction
+/// included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Input { steps: u64, value: i64 },
+    Output { steps: u64, value: i64 },
+    Halt { steps: u64 }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Input { steps, value } => write!(f, "{} in {}", steps, value),
+            Event::Output { steps, value } => write!(f, "{} out {}", steps, value),
+            Event::Halt { steps } => write!(f, "{} halt", steps)
+        }
+    }
+}
+
+/// A recorded run, see `Recorder`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    /// The machine before the first event, without queued inputs.
+    pub start: Snapshot,
+    pub events: Vec<Event>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    Vm(VmError),
+    /// The run did something else than the recording at event `index`. `None` stands for the end
+    /// of the recording or the machine halting without an event.
+    Mismatch { index: usize, expected: Option<Event>, actual: Option<Event> },
+    /// The program wanted another input at event `index` while the recording only has other
+    /// events left.
+    OutOfInputs { index: usize }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let event = |event: &Option<Event>| event.map_or("the end".to_string(), |e| format!("`{}`", e));
+        match self {
+            ReplayError::Vm(e) => write!(f, "{}", e),
+            ReplayError::Mismatch { index, expected, actual } => {
+                write!(f, "replay differs at event {}: expected {}, got {}", index, event(expected), event(actual))
+            },
+            ReplayError::OutOfInputs { index } => write!(f, "replay wants more inputs than recorded at event {}", index)
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl From<VmError> for ReplayError {
+    fn from(e: VmError) -> Self {
+        ReplayError::Vm(e)
+    }
+}
+
+/// Runs a machine while recording its I/O into a `Session`.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    machine: Machine,
+    session: Session,
+    /// `machine.steps()` when recording started.
+    base_steps: u64,
+    halted: bool
+}
+
+impl Recorder {
+    /// Starts recording `machine` in its current state. Inputs already queued are recorded when
+    /// the program reads them, like inputs pushed later.
+    pub fn new(machine: Machine) -> Self {
+        let mut start = machine.snapshot();
+        start.inputs.clear();
+        let base_steps = machine.steps();
+        Recorder { machine, session: Session { start, events: Vec::new() }, base_steps, halted: false }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    pub fn into_session(self) -> Session {
+        self.session
+    }
+
+    pub fn push_input(&mut self, value: i64) {
+        self.machine.push_input(value);
+    }
+
+    /// Like `Machine::run`, recording what happened.
+    pub fn run(&mut self) -> Result<StopReason, VmError> {
+        let mut steps = self.machine.steps() - self.base_steps;
+        let events = &mut self.session.events;
+        let reason = self.machine.run_traced(&mut |step: &TraceStep| {
+            steps += 1;
+            if let (Opcode::Input, Some((_, value))) = (step.instruction.op, step.write) {
+                events.push(Event::Input { steps, value });
+            }
+        })?;
+
+        let steps = self.machine.steps() - self.base_steps;
+        match reason {
+            StopReason::Output(value) => self.session.events.push(Event::Output { steps, value }),
+            StopReason::Halted if !self.halted => {
+                self.halted = true;
+                self.session.events.push(Event::Halt { steps });
+            },
+            _ => {}
+        }
+        Ok(reason)
+    }
+}
+
+impl Session {
+    /// Runs the recording again from its snapshot, feeding it the recorded inputs whenever the
+    /// program asks for one and checking it produces the same events at the same step counts.
+    ///
+    /// A recording that stops while the program waits for input, like day 15's, replays fine up
+    /// to there.
+    pub fn replay(&self) -> Result<(), ReplayError> {
+        self.replay_on(Machine::from_snapshot(&self.start))
+    }
+
+    /// Like `replay`, on `machine` instead of a fresh one from the snapshot. The machine has to
+    /// be in the recorded starting state, its settings like a step limit or a decode cache can
+    /// differ.
+    pub fn replay_on(&self, machine: Machine) -> Result<(), ReplayError> {
+        let mut inputs = self.events.iter().filter_map(|event| match event {
+            Event::Input { value, .. } => Some(*value),
+            _ => None
+        });
+        let mut recorder = Recorder::new(machine);
+        let mut checked = 0;
+        loop {
+            let reason = recorder.run()?;
+            let events = &recorder.session.events;
+            for (index, actual) in events.iter().enumerate().skip(checked) {
+                let expected = self.events.get(index);
+                if expected != Some(actual) {
+                    return Err(ReplayError::Mismatch { index, expected: expected.copied(), actual: Some(*actual) });
+                }
+            }
+            checked = events.len();
+
+            match reason {
+                StopReason::NeedInput => match inputs.next() {
+                    Some(value) => recorder.push_input(value),
+                    None if checked == self.events.len() => return Ok(()),
+                    None => return Err(ReplayError::OutOfInputs { index: checked })
+                },
+                StopReason::Halted => {
+                    return match self.events.get(checked) {
+                        Some(expected) => Err(ReplayError::Mismatch { index: checked, expected: Some(*expected), actual: None }),
+                        None => Ok(())
+                    };
+                },
+                StopReason::Output(_) => {}
+            }
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{}", HEADER)?;
+        self.start.write_to(&mut writer)?;
+        for event in &self.events {
+            writeln!(writer, "{}", event)?;
+        }
+        writer.flush()
+    }
+
+    /// Errors are `SnapshotError`s, with line numbers counted from the start of the session.
+    pub fn read_from<R: Read>(reader: R) -> Result<Session, SnapshotError> {
+        let lines = BufReader::new(reader).lines().collect::<io::Result<Vec<String>>>()?;
+        let bad_format = |index: usize, message: String| SnapshotError::BadFormat { line: index + 1, message };
+
+        if lines.first().map(|l| l.as_str()) != Some(HEADER) {
+            return Err(bad_format(0, format!("expected `{}`", HEADER)));
+        }
+        let snapshot_end = lines.len().min(SNAPSHOT_LINES + 1);
+        let start = Snapshot::read_from(lines[1..snapshot_end].join("\n").as_bytes()).map_err(|e| match e {
+            SnapshotError::BadFormat { line, message } => SnapshotError::BadFormat { line: line + 1, message },
+            e => e
+        })?;
+
+        let mut events = Vec::new();
+        for (index, line) in lines.iter().enumerate().skip(snapshot_end) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let number = |s: &str| s.parse().map_err(|_| bad_format(index, format!("`{}` isn't a number", s)));
+            let step_count = |s: &str| s.parse().map_err(|_| bad_format(index, format!("`{}` isn't a step count", s)));
+            events.push(match parts[..] {
+                [steps, "in", value] => Event::Input { steps: step_count(steps)?, value: number(value)? },
+                [steps, "out", value] => Event::Output { steps: step_count(steps)?, value: number(value)? },
+                [steps, "halt"] => Event::Halt { steps: step_count(steps)? },
+                [] => continue,
+                _ => return Err(bad_format(index, format!("expected `<steps> in|out <value>` or `<steps> halt`, found `{}`", line)))
+            });
+        }
+        Ok(Session { start, events })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Session, SnapshotError> {
+        Session::read_from(File::open(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::session::{Event, Recorder, ReplayError, Session};
+    use crate::{parse_program, Machine, SnapshotError, StopReason};
+
+    /// Doubles inputs until it reads a 0.
+    const DOUBLER: &str = "3,15,1006,15,14,1002,15,2,16,4,16,1105,1,0,99,0,0";
+
+    fn record(inputs: &[i64]) -> Session {
+        let mut m = Machine::new(&parse_program(DOUBLER));
+        m.push_input(inputs[0]);
+        let mut recorder = Recorder::new(m);
+        let mut inputs = inputs[1..].iter();
+        loop {
+            match recorder.run().unwrap() {
+                StopReason::NeedInput => recorder.push_input(*inputs.next().unwrap()),
+                StopReason::Output(_) => {},
+                StopReason::Halted => return recorder.into_session()
+            }
+        }
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let session = record(&[3, 4, 0]);
+        assert_eq!(session.events, vec![
+            Event::Input { steps: 1, value: 3 },
+            Event::Output { steps: 4, value: 6 },
+            Event::Input { steps: 6, value: 4 },
+            Event::Output { steps: 9, value: 8 },
+            Event::Input { steps: 11, value: 0 },
+            Event::Halt { steps: 13 }
+        ]);
+        assert_eq!(session.replay(), Ok(()));
+
+        let mut bytes = Vec::new();
+        session.write_to(&mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("intcode-session 1\nintcode-snapshot 1\n"));
+        assert!(text.ends_with("\n1 in 3\n4 out 6\n6 in 4\n9 out 8\n11 in 0\n13 halt\n"));
+        assert_eq!(Session::read_from(text.as_bytes()).unwrap(), session);
+        let negative = text.replace("\n13 halt", "\n-1 halt");
+        match Session::read_from(negative.as_bytes()) {
+            Err(SnapshotError::BadFormat { line, message }) => assert_eq!((line, message.as_str()), (13, "`-1` isn't a step count")),
+            other => panic!("{:?}", other)
+        }
+
+        let mut cached = Machine::from_snapshot(&session.start);
+        cached.set_decode_cache(true);
+        assert_eq!(session.replay_on(cached), Ok(()));
+    }
+
+    #[test]
+    fn day15_exploration() {
+        // recorded with `day15-part1 --record ../part1.session`
+        let session = Session::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../day15/part1.session")).unwrap();
+        assert_eq!(session.replay(), Ok(()));
+    }
+
+    #[test]
+    fn replay_catches_differences() {
+        let mut session = record(&[3, 0]);
+        // a different program at the start, tripling instead
+        session.start.memory[7] = 3;
+        let e = session.replay().unwrap_err();
+        assert_eq!(e, ReplayError::Mismatch {
+            index: 1,
+            expected: Some(Event::Output { steps: 4, value: 6 }),
+            actual: Some(Event::Output { steps: 4, value: 9 })
+        });
+        assert_eq!(e.to_string(), "replay differs at event 1: expected `4 out 6`, got `4 out 9`");
+
+        let mut session = record(&[3, 0]);
+        session.events.truncate(2);
+        assert_eq!(session.replay(), Ok(()));
+
+        let mut session = record(&[3, 0]);
+        session.events.remove(2);
+        assert_eq!(session.replay(), Err(ReplayError::OutOfInputs { index: 2 }));
+
+        let mut session = record(&[3, 0]);
+        session.events.push(Event::Output { steps: 14, value: 1 });
+        assert_eq!(session.replay(), Err(ReplayError::Mismatch { index: 4, expected: Some(Event::Output { steps: 14, value: 1 }), actual: None }));
+    }
+}
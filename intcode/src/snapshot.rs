@@ -0,0 +1,154 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const HEADER: &str = "intcode-snapshot 1";
+
+/// Everything needed to resume a `Machine` later, see `Machine::snapshot`.
+///
+/// Outputs are handed to the caller as soon as they're produced, so the only pending I/O is the
+/// input queue. Decoded instructions aren't part of a snapshot, a restored machine decodes again.
+///
+/// The file format is line based text:
+///
+/// ```text
+/// intcode-snapshot 1
+/// ip 25
+/// rb 1000
+/// limit 16777216
+/// inputs 1,2
+/// memory 1102,34463338,34463338,63,...
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub memory: Vec<i64>,
+    pub memory_limit: usize,
+    pub ip: i64,
+    pub relative_base: i64,
+    pub inputs: Vec<i64>
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    /// The file isn't a snapshot. `line` is 1-based.
+    BadFormat { line: usize, message: String }
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "couldn't access snapshot: {}", e),
+            SnapshotError::BadFormat { line, message } => write!(f, "bad snapshot at line {}: {}", line, message)
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+fn join(values: &[i64]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(",")
+}
+
+impl Snapshot {
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{}", HEADER)?;
+        writeln!(writer, "ip {}", self.ip)?;
+        writeln!(writer, "rb {}", self.relative_base)?;
+        writeln!(writer, "limit {}", self.memory_limit)?;
+        writeln!(writer, "inputs {}", join(&self.inputs))?;
+        writeln!(writer, "memory {}", join(&self.memory))?;
+        writer.flush()
+    }
+
+    pub fn read_from<R: Read>(reader: R) -> Result<Snapshot, SnapshotError> {
+        let lines = BufReader::new(reader).lines().collect::<io::Result<Vec<String>>>()?;
+        let bad_format = |index: usize, message: String| SnapshotError::BadFormat { line: index + 1, message };
+
+        if lines.first().map(|l| l.as_str()) != Some(HEADER) {
+            return Err(bad_format(0, format!("expected `{}`", HEADER)));
+        }
+
+        // the value after `key` on the line at `index`
+        let field = |index: usize, key: &str| -> Result<&str, SnapshotError> {
+            let line = lines.get(index).ok_or_else(|| bad_format(index, format!("expected `{}`, found the end of the file", key)))?;
+            let mut parts = line.splitn(2, ' ');
+            if parts.next() != Some(key) {
+                return Err(bad_format(index, format!("expected `{}`", key)));
+            }
+            Ok(parts.next().unwrap_or("").trim())
+        };
+        let number = |index: usize, s: &str| s.parse().map_err(|_| bad_format(index, format!("`{}` isn't a number", s)));
+        let list = |index: usize, s: &str| -> Result<Vec<i64>, SnapshotError> {
+            if s.is_empty() {
+                return Ok(Vec::new());
+            }
+            s.split(',').map(|value| number(index, value.trim())).collect()
+        };
+
+        Ok(Snapshot {
+            ip: number(1, field(1, "ip")?)?,
+            relative_base: number(2, field(2, "rb")?)?,
+            memory_limit: field(3, "limit")?.parse().map_err(|_| bad_format(3, "the limit isn't a number".to_string()))?,
+            inputs: list(4, field(4, "inputs")?)?,
+            memory: list(5, field(5, "memory")?)?
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Snapshot, SnapshotError> {
+        Snapshot::read_from(File::open(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::snapshot::{Snapshot, SnapshotError};
+    use crate::{parse_program, Machine, StopReason};
+
+    #[test]
+    fn fork_and_resume() {
+        // echoes inputs forever
+        let mut m = Machine::new(&parse_program("3,10,4,10,1105,1,0"));
+        m.push_input(1);
+        m.push_input(2);
+        assert_eq!(m.run(), Ok(StopReason::Output(1)));
+        let snapshot = m.snapshot();
+
+        assert_eq!(m.run(), Ok(StopReason::Output(2)));
+        assert_eq!(m.run(), Ok(StopReason::NeedInput));
+
+        m.restore(&snapshot);
+        assert_eq!(m.memory().as_slice()[10], 1);
+        assert_eq!(m.run(), Ok(StopReason::Output(2)));
+
+        let mut bytes = Vec::new();
+        snapshot.write_to(&mut bytes).unwrap();
+        let mut copy = Machine::from_snapshot(&Snapshot::read_from(&bytes[..]).unwrap());
+        assert_eq!(copy.snapshot(), snapshot);
+        assert_eq!(copy.run(), Ok(StopReason::Output(2)));
+    }
+
+    #[test]
+    fn bad_files() {
+        let error = |text: &str| match Snapshot::read_from(text.as_bytes()) {
+            Err(SnapshotError::BadFormat { line, .. }) => line,
+            r => panic!("expected a format error, got {:?}", r)
+        };
+        assert_eq!(error("1,2,3"), 1);
+        assert_eq!(error("intcode-snapshot 1\nip 0\nrb x"), 3);
+        assert_eq!(error("intcode-snapshot 1\nip 0\nrb 0\nlimit 10\ninputs\n"), 6);
+        assert!(Snapshot::read_from("intcode-snapshot 1\nip 0\nrb 0\nlimit 10\ninputs\nmemory 99".as_bytes()).is_ok());
+    }
+}
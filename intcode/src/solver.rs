@@ -0,0 +1,356 @@
+//! A small solver for `symbolic` constraints over bounded integers.
+//!
+//! Linear constraints narrow down the range of every symbol they use until nothing changes.
+//! Then the search picks the symbol with the smallest range and splits it, trying values close
+//! to 0 first. Constraints that aren't linear, like a product of two symbols, are only checked
+//! once all their symbols have a value, so those can make the search give up.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::RangeInclusive;
+use crate::symbolic::{Constraint, Expr, Symbol};
+
+/// A value for every symbol the constraints use.
+pub type Model = BTreeMap<Symbol, i64>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Solution {
+    Sat(Model),
+    Unsat,
+    /// The search ran out of steps.
+    Unknown
+}
+
+/// Ranges the search may split before giving up.
+const SEARCH_LIMIT: usize = 100_000;
+
+/// Rounds of narrowing per search step. Constraints like `x < y, y < x` only narrow by one each
+/// round, splitting gets through those quicker.
+const PROPAGATION_ROUNDS: usize = 64;
+
+/// `constant + coefficient * symbol + ...`, symbols by index.
+#[derive(Debug, Clone, Default)]
+struct Linear {
+    constant: i128,
+    terms: BTreeMap<usize, i128>
+}
+
+impl Linear {
+    fn plus(mut self, other: Linear, sign: i128) -> Linear {
+        self.constant += sign * other.constant;
+        for (symbol, c) in other.terms {
+            *self.terms.entry(symbol).or_insert(0) += sign * c;
+        }
+        self.terms.retain(|_, c| *c != 0);
+        self
+    }
+}
+
+fn linear(expr: &Expr, index: &BTreeMap<Symbol, usize>) -> Option<Linear> {
+    Some(match expr {
+        Expr::Const(value) => Linear { constant: *value as i128, terms: BTreeMap::new() },
+        Expr::Symbol(symbol) => {
+            let mut terms = BTreeMap::new();
+            terms.insert(index[symbol], 1);
+            Linear { constant: 0, terms }
+        },
+        Expr::Add(a, b) => linear(a, index)?.plus(linear(b, index)?, 1),
+        Expr::Multiply(a, b) => {
+            let (a, b) = (linear(a, index)?, linear(b, index)?);
+            let (scale, mut l) = match (a.terms.is_empty(), b.terms.is_empty()) {
+                (true, _) => (a.constant, b),
+                (_, true) => (b.constant, a),
+                _ => return None
+            };
+            l.constant = l.constant.checked_mul(scale)?;
+            for c in l.terms.values_mut() {
+                *c = c.checked_mul(scale)?;
+            }
+            l.terms.retain(|_, c| *c != 0);
+            l
+        },
+        Expr::LessThan(..) | Expr::Equals(..) => return None
+    })
+}
+
+enum Check {
+    /// The sum is at most 0.
+    AtMostZero(Linear),
+    NotZero(Linear),
+    /// Evaluated once all of the symbols have a value.
+    Other(Constraint, Vec<usize>)
+}
+
+type Ranges = Vec<(i128, i128)>;
+
+enum Outcome {
+    Found(Ranges),
+    Failed,
+    OutOfSteps
+}
+
+struct Search {
+    symbols: Vec<Symbol>,
+    checks: Vec<Check>,
+    steps: usize
+}
+
+fn div_floor(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q }
+}
+
+fn div_ceil(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if a % b != 0 && (a < 0) == (b < 0) { q + 1 } else { q }
+}
+
+/// The smallest value `c * x` takes in `range`.
+fn least(c: i128, range: (i128, i128)) -> Option<i128> {
+    Some(c.checked_mul(range.0)?.min(c.checked_mul(range.1)?))
+}
+
+impl Search {
+    /// Narrows ranges so `l <= 0` can still hold, false if it can't. Sums too big to work out are
+    /// left alone.
+    fn narrow(l: &Linear, ranges: &mut Ranges, changed: &mut bool) -> bool {
+        let mut total = Some(l.constant);
+        for (symbol, c) in &l.terms {
+            total = total.and_then(|t| t.checked_add(least(*c, ranges[*symbol])?));
+        }
+        let total = match total {
+            Some(total) => total,
+            None => return true
+        };
+        if total > 0 {
+            return false;
+        }
+
+        for (symbol, c) in &l.terms {
+            // c * x <= -(everything else at its least)
+            let bound = -(total - least(*c, ranges[*symbol]).unwrap());
+            let range = &mut ranges[*symbol];
+            if *c > 0 && div_floor(bound, *c) < range.1 {
+                range.1 = div_floor(bound, *c);
+                *changed = true;
+            } else if *c < 0 && div_ceil(bound, *c) > range.0 {
+                range.0 = div_ceil(bound, *c);
+                *changed = true;
+            }
+            if range.0 > range.1 {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Keeps the one open symbol of `l != 0` off the value that would make it 0.
+    fn exclude(l: &Linear, ranges: &mut Ranges, changed: &mut bool) -> bool {
+        let mut sum = l.constant;
+        let mut open = None;
+        for (symbol, c) in &l.terms {
+            let range = ranges[*symbol];
+            if range.0 == range.1 {
+                sum += c * range.0;
+            } else if open.is_some() {
+                return true;
+            } else {
+                open = Some((*symbol, *c));
+            }
+        }
+
+        let (symbol, c) = match open {
+            Some(open) => open,
+            None => return sum != 0
+        };
+        if sum % c != 0 {
+            return true;
+        }
+        let range = &mut ranges[symbol];
+        if range.0 == -sum / c {
+            range.0 += 1;
+            *changed = true;
+        } else if range.1 == -sum / c {
+            range.1 -= 1;
+            *changed = true;
+        }
+        range.0 <= range.1
+    }
+
+    fn propagate(&self, ranges: &mut Ranges) -> bool {
+        for _ in 0..PROPAGATION_ROUNDS {
+            let mut changed = false;
+            for check in &self.checks {
+                let ok = match check {
+                    Check::AtMostZero(l) => Search::narrow(l, ranges, &mut changed),
+                    Check::NotZero(l) => Search::exclude(l, ranges, &mut changed),
+                    Check::Other(constraint, symbols) => {
+                        if symbols.iter().all(|s| ranges[*s].0 == ranges[*s].1) {
+                            let value = |symbol| ranges[self.symbols.binary_search(&symbol).unwrap()].0 as i64;
+                            constraint.eval(&value) == Some(true)
+                        } else {
+                            true
+                        }
+                    }
+                };
+                if !ok {
+                    return false;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        true
+    }
+
+    fn search(&mut self, mut ranges: Ranges) -> Outcome {
+        self.steps += 1;
+        if self.steps > SEARCH_LIMIT {
+            return Outcome::OutOfSteps;
+        }
+        if !self.propagate(&mut ranges) {
+            return Outcome::Failed;
+        }
+
+        let open = (0..ranges.len())
+            .filter(|s| ranges[*s].0 < ranges[*s].1)
+            .min_by_key(|s| ranges[*s].1 - ranges[*s].0);
+        let symbol = match open {
+            Some(symbol) => symbol,
+            None => return Outcome::Found(ranges)
+        };
+
+        // 0 first, then upwards, then downwards
+        let (low, high) = ranges[symbol];
+        let mid = low + (high - low) / 2;
+        let parts = if low <= 0 && 0 <= high {
+            vec![(0, 0), (1, high), (low, -1)]
+        } else if low > 0 {
+            vec![(low, mid), (mid + 1, high)]
+        } else {
+            vec![(mid + 1, high), (low, mid)]
+        };
+        for part in parts {
+            if part.0 > part.1 {
+                continue;
+            }
+            let mut split = ranges.clone();
+            split[symbol] = part;
+            match self.search(split) {
+                Outcome::Failed => {},
+                outcome => return outcome
+            }
+        }
+        Outcome::Failed
+    }
+}
+
+/// Looks for values within `bounds` for the symbols in `constraints` that make them all hold.
+pub fn solve<B>(constraints: &[Constraint], bounds: B) -> Solution
+    where B: Fn(Symbol) -> RangeInclusive<i64>
+{
+    let mut used = BTreeSet::new();
+    for constraint in constraints {
+        constraint.expr.symbols(&mut used);
+    }
+    let symbols: Vec<Symbol> = used.into_iter().collect();
+    let index: BTreeMap<Symbol, usize> = symbols.iter().enumerate().map(|(i, s)| (*s, i)).collect();
+
+    let mut checks = Vec::new();
+    for constraint in constraints {
+        let comparison = match &constraint.expr {
+            Expr::LessThan(a, b) | Expr::Equals(a, b) => linear(a, &index).zip(linear(b, &index)),
+            _ => None
+        };
+        match (&constraint.expr, constraint.holds, comparison) {
+            // a < b is a - b + 1 <= 0, a >= b is b - a <= 0
+            (Expr::LessThan(..), true, Some((a, b))) => {
+                let one = Linear { constant: 1, terms: BTreeMap::new() };
+                checks.push(Check::AtMostZero(a.plus(b, -1).plus(one, 1)));
+            },
+            (Expr::LessThan(..), false, Some((a, b))) => checks.push(Check::AtMostZero(b.plus(a, -1))),
+            (Expr::Equals(..), true, Some((a, b))) => {
+                checks.push(Check::AtMostZero(a.clone().plus(b.clone(), -1)));
+                checks.push(Check::AtMostZero(b.plus(a, -1)));
+            },
+            (Expr::Equals(..), false, Some((a, b))) => checks.push(Check::NotZero(a.plus(b, -1))),
+            (expr, holds, _) => match linear(expr, &index) {
+                Some(l) if holds => checks.push(Check::NotZero(l)),
+                Some(l) => {
+                    checks.push(Check::AtMostZero(l.clone()));
+                    checks.push(Check::AtMostZero(Linear::default().plus(l, -1)));
+                },
+                None => {
+                    let mut used = BTreeSet::new();
+                    constraint.expr.symbols(&mut used);
+                    checks.push(Check::Other(constraint.clone(), used.iter().map(|s| index[s]).collect()));
+                }
+            }
+        }
+    }
+
+    let ranges: Ranges = symbols.iter().map(|s| {
+        let bounds = bounds(*s);
+        (*bounds.start() as i128, *bounds.end() as i128)
+    }).collect();
+    if ranges.iter().any(|r| r.0 > r.1) {
+        return Solution::Unsat;
+    }
+
+    let mut search = Search { symbols, checks, steps: 0 };
+    match search.search(ranges) {
+        Outcome::Found(ranges) => Solution::Sat(
+            search.symbols.iter().zip(ranges).map(|(s, r)| (*s, r.0 as i64)).collect()
+        ),
+        Outcome::Failed => Solution::Unsat,
+        Outcome::OutOfSteps => Solution::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::solver::{solve, Solution};
+    use crate::symbolic::{Constraint, Expr, Symbol};
+
+    fn x(n: usize) -> Expr {
+        Expr::Symbol(Symbol::Input(n))
+    }
+
+    fn c(value: i64) -> Expr {
+        Expr::Const(value)
+    }
+
+    #[test]
+    fn linear_constraints() {
+        // 3x + 2y == 31, x < y, x != 3
+        let constraints = vec![
+            Constraint::new(Expr::equals(Expr::sum(Expr::product(x(0), c(3)).unwrap(), Expr::product(c(2), x(1)).unwrap()).unwrap(), c(31)), true),
+            Constraint::new(Expr::less_than(x(0), x(1)), true),
+            Constraint::new(Expr::equals(x(0), c(3)), false)
+        ];
+        match solve(&constraints, |_| 0..=100) {
+            Solution::Sat(model) => {
+                let (a, b) = (model[&Symbol::Input(0)], model[&Symbol::Input(1)]);
+                assert!(3 * a + 2 * b == 31 && a < b && a != 3, "{} {}", a, b);
+            },
+            other => panic!("{:?}", other)
+        }
+        assert_eq!(solve(&constraints, |_| 0..=4), Solution::Unsat);
+    }
+
+    #[test]
+    fn other_constraints() {
+        // x * y == 91 with both above 1
+        let constraints = vec![
+            Constraint::new(Expr::equals(Expr::product(x(0), x(1)).unwrap(), c(91)), true),
+            Constraint::new(Expr::less_than(c(1), x(0)), true),
+            Constraint::new(Expr::less_than(c(1), x(1)), true)
+        ];
+        match solve(&constraints, |_| -100..=100) {
+            Solution::Sat(model) => assert_eq!(model[&Symbol::Input(0)] * model[&Symbol::Input(1)], 91),
+            other => panic!("{:?}", other)
+        }
+        assert_eq!(solve(&[Constraint::new(c(0), true)], |_| 0..=0), Solution::Unsat);
+        assert_eq!(solve(&[], |_| 0..=0), Solution::Sat(Default::default()));
+    }
+}
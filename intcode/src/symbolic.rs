@@ -0,0 +1,634 @@
+//! Symbolic execution of programs.
+//!
+//! Inputs and chosen memory cells are symbols instead of numbers, and arithmetic on them builds
+//! expressions. A jump whose condition depends on a symbol forks the run: one path goes on with
+//! the jump taken and the other without, each recording the condition as a constraint. Paths
+//! whose constraints `solver::solve` shows can't all hold are dropped.
+//!
+//! Addresses, opcodes, jump targets and the relative base have to stay concrete. A path that
+//! needs one of them to be symbolic ends as `PathEnd::Unsupported`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use crate::decode::{decode, DecodeError, Opcode, ParameterMode};
+use crate::error::VmError;
+use crate::memory::DEFAULT_MEMORY_LIMIT;
+use crate::solver::{self, Model, Solution};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Symbol {
+    /// The `n`th value read by opcode 3, counting from 0.
+    Input(usize),
+    /// The value a memory cell starts with.
+    Cell(i64)
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Symbol::Input(n) => write!(f, "in{}", n),
+            Symbol::Cell(address) => write!(f, "[{}]", address)
+        }
+    }
+}
+
+/// A value computed from symbols, named after the opcodes that build it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Const(i64),
+    Symbol(Symbol),
+    Add(Rc<Expr>, Rc<Expr>),
+    Multiply(Rc<Expr>, Rc<Expr>),
+    LessThan(Rc<Expr>, Rc<Expr>),
+    Equals(Rc<Expr>, Rc<Expr>)
+}
+
+impl Expr {
+    pub fn constant(&self) -> Option<i64> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    /// Whether the value is always 0 or 1.
+    pub fn is_boolean(&self) -> bool {
+        match self {
+            Expr::Const(value) => *value == 0 || *value == 1,
+            Expr::LessThan(..) | Expr::Equals(..) => true,
+            _ => false
+        }
+    }
+
+    /// `a + b`, folding constants. `None` if adding two constants overflows.
+    pub fn sum(a: Expr, b: Expr) -> Option<Expr> {
+        Some(match (a.constant(), b.constant()) {
+            (Some(x), Some(y)) => Expr::Const(x.checked_add(y)?),
+            (Some(0), _) => b,
+            (_, Some(0)) => a,
+            _ => Expr::Add(Rc::new(a), Rc::new(b))
+        })
+    }
+
+    /// `a * b`, folding constants. `None` if multiplying two constants overflows.
+    pub fn product(a: Expr, b: Expr) -> Option<Expr> {
+        Some(match (a.constant(), b.constant()) {
+            (Some(x), Some(y)) => Expr::Const(x.checked_mul(y)?),
+            (Some(0), _) | (_, Some(0)) => Expr::Const(0),
+            (Some(1), _) => b,
+            (_, Some(1)) => a,
+            _ => Expr::Multiply(Rc::new(a), Rc::new(b))
+        })
+    }
+
+    pub fn less_than(a: Expr, b: Expr) -> Expr {
+        match (a.constant(), b.constant()) {
+            (Some(x), Some(y)) => Expr::Const((x < y) as i64),
+            _ if a == b => Expr::Const(0),
+            _ => Expr::LessThan(Rc::new(a), Rc::new(b))
+        }
+    }
+
+    pub fn equals(a: Expr, b: Expr) -> Expr {
+        match (a.constant(), b.constant()) {
+            (Some(x), Some(y)) => Expr::Const((x == y) as i64),
+            _ if a == b => Expr::Const(1),
+            _ => Expr::Equals(Rc::new(a), Rc::new(b))
+        }
+    }
+
+    /// Adds every symbol the expression uses to `symbols`.
+    pub fn symbols(&self, symbols: &mut BTreeSet<Symbol>) {
+        match self {
+            Expr::Const(_) => {},
+            Expr::Symbol(symbol) => {
+                symbols.insert(*symbol);
+            },
+            Expr::Add(a, b) | Expr::Multiply(a, b) | Expr::LessThan(a, b) | Expr::Equals(a, b) => {
+                a.symbols(symbols);
+                b.symbols(symbols);
+            }
+        }
+    }
+
+    /// The value with `value` giving every symbol's, `None` on overflow.
+    pub fn eval(&self, value: &dyn Fn(Symbol) -> i64) -> Option<i64> {
+        Some(match self {
+            Expr::Const(c) => *c,
+            Expr::Symbol(symbol) => value(*symbol),
+            Expr::Add(a, b) => a.eval(value)?.checked_add(b.eval(value)?)?,
+            Expr::Multiply(a, b) => a.eval(value)?.checked_mul(b.eval(value)?)?,
+            Expr::LessThan(a, b) => (a.eval(value)? < b.eval(value)?) as i64,
+            Expr::Equals(a, b) => (a.eval(value)? == b.eval(value)?) as i64
+        })
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Const(value) => write!(f, "{}", value),
+            Expr::Symbol(symbol) => write!(f, "{}", symbol),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Multiply(a, b) => write!(f, "({} * {})", a, b),
+            Expr::LessThan(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Equals(a, b) => write!(f, "({} == {})", a, b)
+        }
+    }
+}
+
+/// `expr` is non-zero if `holds` is set, zero otherwise, like the condition of a jump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub expr: Expr,
+    pub holds: bool
+}
+
+impl Constraint {
+    /// Strips comparisons of a comparison's result with 0 or 1, which programs use to negate
+    /// conditions, so the solver sees the comparison itself.
+    pub fn new(mut expr: Expr, mut holds: bool) -> Self {
+        loop {
+            let (inner, negate) = match &expr {
+                Expr::Equals(a, b) if a.is_boolean() && b.constant() == Some(0) => (a.clone(), true),
+                Expr::Equals(a, b) if b.is_boolean() && a.constant() == Some(0) => (b.clone(), true),
+                Expr::Equals(a, b) if a.is_boolean() && b.constant() == Some(1) => (a.clone(), false),
+                Expr::Equals(a, b) if b.is_boolean() && a.constant() == Some(1) => (b.clone(), false),
+                Expr::LessThan(a, b) if a.is_boolean() && b.constant() == Some(1) => (a.clone(), true),
+                Expr::LessThan(a, b) if b.is_boolean() && a.constant() == Some(0) => (b.clone(), false),
+                _ => return Constraint { expr, holds }
+            };
+            expr = (*inner).clone();
+            holds ^= negate;
+        }
+    }
+
+    /// Whether the constraint holds with `value` giving every symbol's, `None` on overflow.
+    pub fn eval(&self, value: &dyn Fn(Symbol) -> i64) -> Option<bool> {
+        self.expr.eval(value).map(|result| (result != 0) == self.holds)
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.expr, self.holds) {
+            (Expr::LessThan(a, b), true) => write!(f, "{} < {}", a, b),
+            (Expr::LessThan(a, b), false) => write!(f, "{} >= {}", a, b),
+            (Expr::Equals(a, b), true) => write!(f, "{} == {}", a, b),
+            (Expr::Equals(a, b), false) => write!(f, "{} != {}", a, b),
+            (expr, true) => write!(f, "{} != 0", expr),
+            (expr, false) => write!(f, "{} == 0", expr)
+        }
+    }
+}
+
+/// A value opcode 4 produced on a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub value: Expr,
+    /// How many of the path's constraints had been recorded, the rest came later.
+    pub constraints: usize,
+    /// How many inputs had been read.
+    pub inputs: usize
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathEnd {
+    Halted,
+    /// Wanted more inputs than `Executor::set_max_inputs` allows.
+    InputLimit,
+    /// Ran for `Executor::set_max_steps` instructions.
+    StepLimit,
+    /// Needed a symbolic opcode, address, jump target or relative base adjustment.
+    Unsupported { ip: i64, reason: &'static str },
+    /// Failed like the interpreter would have.
+    Fault(VmError)
+}
+
+impl fmt::Display for PathEnd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathEnd::Halted => write!(f, "halted"),
+            PathEnd::InputLimit => write!(f, "wants more inputs"),
+            PathEnd::StepLimit => write!(f, "ran out of steps"),
+            PathEnd::Unsupported { ip, reason } => write!(f, "stopped at ip {}: {}", ip, reason),
+            PathEnd::Fault(e) => write!(f, "{}", e)
+        }
+    }
+}
+
+/// One way through the program, see `Executor::explore`.
+#[derive(Debug, Clone)]
+pub struct Path {
+    /// Jump conditions along the path, in order.
+    pub constraints: Vec<Constraint>,
+    pub outputs: Vec<Output>,
+    /// Number of inputs read, `Symbol::Input(0)` up to this.
+    pub inputs: usize,
+    pub end: PathEnd,
+    program: Rc<[i64]>,
+    memory: BTreeMap<i64, Expr>
+}
+
+impl Path {
+    /// The cell at `address` when the path ended.
+    pub fn read(&self, address: i64) -> Expr {
+        read(&self.program, &self.memory, address)
+    }
+}
+
+/// Values for the symbols that send a run down some path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Witness {
+    pub inputs: Vec<i64>,
+    /// The symbolic cells' starting values by address.
+    pub cells: BTreeMap<i64, i64>
+}
+
+fn read(program: &[i64], memory: &BTreeMap<i64, Expr>, address: i64) -> Expr {
+    match memory.get(&address) {
+        Some(value) => value.clone(),
+        None => Expr::Const(program.get(address as usize).copied().unwrap_or(0))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    /// Cells that differ from the program image.
+    memory: BTreeMap<i64, Expr>,
+    ip: i64,
+    relative_base: i64,
+    inputs: usize,
+    steps: usize,
+    constraints: Vec<Constraint>,
+    outputs: Vec<Output>
+}
+
+/// A jump on a symbolic condition.
+struct Fork {
+    condition: Expr,
+    /// Whether the jump is taken when `condition` is non-zero.
+    jump_if_true: bool,
+    target: i64
+}
+
+pub const DEFAULT_MAX_INPUTS: usize = 16;
+pub const DEFAULT_MAX_STEPS: usize = 1_000_000;
+pub const DEFAULT_MAX_PATHS: usize = 1_000;
+
+#[derive(Debug, Clone)]
+pub struct Executor {
+    program: Rc<[i64]>,
+    cells: BTreeMap<i64, RangeInclusive<i64>>,
+    input_bounds: RangeInclusive<i64>,
+    max_inputs: usize,
+    max_steps: usize,
+    max_paths: usize
+}
+
+impl Executor {
+    /// Inputs are symbols between the `i32` limits until `set_input_bounds` says otherwise.
+    pub fn new(program: &[i64]) -> Self {
+        Executor {
+            program: program.into(),
+            cells: BTreeMap::new(),
+            input_bounds: i32::MIN as i64..=i32::MAX as i64,
+            max_inputs: DEFAULT_MAX_INPUTS,
+            max_steps: DEFAULT_MAX_STEPS,
+            max_paths: DEFAULT_MAX_PATHS
+        }
+    }
+
+    pub fn set_input_bounds(&mut self, bounds: RangeInclusive<i64>) {
+        self.input_bounds = bounds;
+    }
+
+    /// Makes the cell at `address` a symbol with a value in `bounds`.
+    pub fn add_symbolic_cell(&mut self, address: i64, bounds: RangeInclusive<i64>) {
+        self.cells.insert(address, bounds);
+    }
+
+    /// Paths that want more inputs end with `PathEnd::InputLimit`.
+    pub fn set_max_inputs(&mut self, max_inputs: usize) {
+        self.max_inputs = max_inputs;
+    }
+
+    /// Instructions per path before it ends with `PathEnd::StepLimit`.
+    pub fn set_max_steps(&mut self, max_steps: usize) {
+        self.max_steps = max_steps;
+    }
+
+    /// `explore` stops after this many paths.
+    pub fn set_max_paths(&mut self, max_paths: usize) {
+        self.max_paths = max_paths;
+    }
+
+    pub fn bounds(&self, symbol: Symbol) -> RangeInclusive<i64> {
+        match symbol {
+            Symbol::Input(_) => self.input_bounds.clone(),
+            Symbol::Cell(address) => self.cells[&address].clone()
+        }
+    }
+
+    pub fn solve(&self, constraints: &[Constraint]) -> Solution {
+        solver::solve(constraints, |symbol| self.bounds(symbol))
+    }
+
+    /// Every path through the program, the path that doesn't jump explored first.
+    ///
+    /// Paths whose constraints can't all hold are left out. Paths the solver couldn't decide on
+    /// are kept.
+    pub fn explore(&self) -> Vec<Path> {
+        let mut memory = BTreeMap::new();
+        for address in self.cells.keys() {
+            memory.insert(*address, Expr::Symbol(Symbol::Cell(*address)));
+        }
+        let mut pending = vec![State {
+            memory,
+            ip: 0,
+            relative_base: 0,
+            inputs: 0,
+            steps: 0,
+            constraints: Vec::new(),
+            outputs: Vec::new()
+        }];
+
+        let mut paths = Vec::new();
+        while let Some(mut state) = pending.pop() {
+            if paths.len() >= self.max_paths {
+                break;
+            }
+            let end = loop {
+                if state.steps >= self.max_steps {
+                    break Some(PathEnd::StepLimit);
+                }
+                state.steps += 1;
+                let fork = match self.step(&mut state) {
+                    Ok(Some(fork)) => fork,
+                    Ok(None) => continue,
+                    Err(end) => break Some(end)
+                };
+
+                let mut jumped = state.clone();
+                jumped.constraints.push(Constraint::new(fork.condition.clone(), fork.jump_if_true));
+                jumped.ip = fork.target;
+                if self.solve(&jumped.constraints) != Solution::Unsat {
+                    pending.push(jumped);
+                }
+                state.constraints.push(Constraint::new(fork.condition, !fork.jump_if_true));
+                state.ip += 3;
+                if self.solve(&state.constraints) == Solution::Unsat {
+                    break None;
+                }
+            };
+
+            if let Some(end) = end {
+                paths.push(Path {
+                    constraints: state.constraints,
+                    outputs: state.outputs,
+                    inputs: state.inputs,
+                    end,
+                    program: self.program.clone(),
+                    memory: state.memory
+                });
+            }
+        }
+        paths
+    }
+
+    /// Values that lead to `model`, symbols it doesn't give a value get the one closest to 0.
+    pub fn witness(&self, model: &Model, inputs: usize) -> Witness {
+        let value = |symbol| match model.get(&symbol) {
+            Some(value) => *value,
+            None => {
+                let bounds = self.bounds(symbol);
+                0.max(*bounds.start()).min(*bounds.end())
+            }
+        };
+        Witness {
+            inputs: (0..inputs).map(|n| value(Symbol::Input(n))).collect(),
+            cells: self.cells.keys().map(|address| (*address, value(Symbol::Cell(*address)))).collect()
+        }
+    }
+
+    /// Inputs and cell values that make the program output `target`, from the first path and
+    /// output where the solver finds some.
+    pub fn find_output(&self, target: i64) -> Option<Witness> {
+        for path in self.explore() {
+            for output in &path.outputs {
+                let mut constraints = path.constraints[..output.constraints].to_vec();
+                constraints.push(Constraint::new(Expr::equals(output.value.clone(), Expr::Const(target)), true));
+                if let Solution::Sat(model) = self.solve(&constraints) {
+                    return Some(self.witness(&model, output.inputs));
+                }
+            }
+        }
+        None
+    }
+
+    fn read(&self, state: &State, address: i64) -> Expr {
+        read(&self.program, &state.memory, address)
+    }
+
+    fn opcode(&self, state: &State) -> i64 {
+        self.read(state, state.ip).constant().unwrap_or(0)
+    }
+
+    fn address(&self, state: &State, parameter: usize, mode: ParameterMode) -> Result<i64, PathEnd> {
+        let ip = state.ip;
+        let word = self.read(state, ip + parameter as i64).constant()
+            .ok_or(PathEnd::Unsupported { ip, reason: "symbolic address" })?;
+        let address = match mode {
+            ParameterMode::Relative => word.checked_add(state.relative_base)
+                .ok_or_else(|| PathEnd::Fault(VmError::Overflow { ip, opcode: self.opcode(state) }))?,
+            _ => word
+        };
+        if address < 0 {
+            return Err(PathEnd::Fault(VmError::NegativeAddress { ip, opcode: self.opcode(state), parameter, address }));
+        }
+        Ok(address)
+    }
+
+    fn get(&self, state: &State, parameter: usize, mode: ParameterMode) -> Result<Expr, PathEnd> {
+        if mode == ParameterMode::Immediate {
+            return Ok(self.read(state, state.ip + parameter as i64));
+        }
+        Ok(self.read(state, self.address(state, parameter, mode)?))
+    }
+
+    fn set(&self, state: &mut State, parameter: usize, mode: ParameterMode, value: Expr) -> Result<(), PathEnd> {
+        let (ip, opcode) = (state.ip, self.opcode(state));
+        if mode == ParameterMode::Immediate {
+            return Err(PathEnd::Fault(VmError::ImmediateWrite { ip, opcode, parameter }));
+        }
+        let address = self.address(state, parameter, mode)?;
+        if address as usize >= DEFAULT_MEMORY_LIMIT.max(self.program.len()) {
+            return Err(PathEnd::Fault(VmError::MemoryLimitExceeded { ip, opcode, parameter, address }));
+        }
+        state.memory.insert(address, value);
+        Ok(())
+    }
+
+    /// Executes one instruction. `Err` ends the path, halting included.
+    fn step(&self, state: &mut State) -> Result<Option<Fork>, PathEnd> {
+        let ip = state.ip;
+        // symbolic cells can be at negative addresses, which the program can never reach
+        let backed = self.program.len().max(state.memory.range(0..).next_back().map_or(0, |(a, _)| *a as usize + 1));
+        if ip < 0 || ip as usize >= backed {
+            return Err(PathEnd::Fault(VmError::IpOutOfRange { ip }));
+        }
+        let opcode = self.read(state, ip).constant().ok_or(PathEnd::Unsupported { ip, reason: "symbolic opcode" })?;
+        let instruction = decode(opcode).map_err(|e| PathEnd::Fault(match e {
+            DecodeError::UnknownOpcode => VmError::UnknownOpcode { ip, opcode },
+            DecodeError::InvalidParameterMode(parameter) => VmError::InvalidParameterMode { ip, opcode, parameter }
+        }))?;
+        let [first, second, third] = instruction.modes;
+        let overflow = || PathEnd::Fault(VmError::Overflow { ip, opcode });
+
+        match instruction.op {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => {
+                let a = self.get(state, 1, first)?;
+                let b = self.get(state, 2, second)?;
+                let value = match instruction.op {
+                    Opcode::Add => Expr::sum(a, b).ok_or_else(overflow)?,
+                    Opcode::Multiply => Expr::product(a, b).ok_or_else(overflow)?,
+                    Opcode::LessThan => Expr::less_than(a, b),
+                    _ => Expr::equals(a, b)
+                };
+                self.set(state, 3, third, value)?;
+                state.ip += 4;
+            },
+            Opcode::Input => {
+                if state.inputs >= self.max_inputs {
+                    return Err(PathEnd::InputLimit);
+                }
+                let value = Expr::Symbol(Symbol::Input(state.inputs));
+                self.set(state, 1, first, value)?;
+                state.inputs += 1;
+                state.ip += 2;
+            },
+            Opcode::Output => {
+                let value = self.get(state, 1, first)?;
+                state.outputs.push(Output { value, constraints: state.constraints.len(), inputs: state.inputs });
+                state.ip += 2;
+            },
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let condition = self.get(state, 1, first)?;
+                let jump_if_true = instruction.op == Opcode::JumpIfTrue;
+                let taken = condition.constant().map(|value| (value != 0) == jump_if_true);
+                if taken == Some(false) {
+                    state.ip += 3;
+                    return Ok(None);
+                }
+                let target = self.get(state, 2, second)?.constant()
+                    .ok_or(PathEnd::Unsupported { ip, reason: "symbolic jump target" })?;
+                if taken.is_none() {
+                    return Ok(Some(Fork { condition, jump_if_true, target }));
+                }
+                state.ip = target;
+            },
+            Opcode::AdjustRelativeBase => {
+                let value = self.get(state, 1, first)?.constant()
+                    .ok_or(PathEnd::Unsupported { ip, reason: "symbolic relative base adjustment" })?;
+                state.relative_base = state.relative_base.checked_add(value).ok_or_else(overflow)?;
+                state.ip += 2;
+            },
+            Opcode::Halt => return Err(PathEnd::Halted)
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::symbolic::{Constraint, Executor, Expr, PathEnd, Symbol};
+    use crate::{parse_program, Machine, StopReason};
+
+    fn outputs(program: &[i64], inputs: &[i64]) -> Vec<i64> {
+        let mut m = Machine::new(program);
+        for input in inputs {
+            m.push_input(*input);
+        }
+        let mut outputs = Vec::new();
+        while let StopReason::Output(value) = m.run().unwrap() {
+            outputs.push(value);
+        }
+        outputs
+    }
+
+    #[test]
+    fn day5_comparisons() {
+        // outputs 999 below 8, 1000 for 8 and 1001 above
+        let program = parse_program("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99");
+        let executor = Executor::new(&program);
+        let paths = executor.explore();
+        assert!(paths.iter().all(|path| path.end == PathEnd::Halted && path.inputs == 1));
+
+        let constraints: Vec<Vec<String>> = paths.iter()
+            .map(|path| path.constraints.iter().map(|c| c.to_string()).collect())
+            .collect();
+        assert_eq!(constraints, vec![
+            vec!["in0 != 8".to_string(), "8 < in0".to_string()],
+            vec!["in0 != 8".to_string(), "8 >= in0".to_string()],
+            vec!["in0 == 8".to_string()]
+        ]);
+
+        for (target, input) in &[(999, 0), (1000, 8), (1001, 9)] {
+            let witness = executor.find_output(*target).unwrap();
+            assert_eq!(witness.inputs, vec![*input]);
+            assert_eq!(outputs(&program, &witness.inputs), vec![*target]);
+        }
+        assert_eq!(executor.find_output(7), None);
+    }
+
+    #[test]
+    fn symbolic_cells() {
+        // outputs 3 * [31] + in0 if that's below 100
+        let mut program = parse_program("3,30,1002,31,3,32,1,32,30,32,1007,32,100,33,1006,33,19,4,32,99");
+        program.resize(34, 0);
+        let mut executor = Executor::new(&program);
+        executor.add_symbolic_cell(31, 0..=40);
+        executor.set_input_bounds(0..=5);
+        let paths = executor.explore();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].outputs[0].value.to_string(), "(([31] * 3) + in0)");
+        assert_eq!(paths[1].constraints[0].to_string(), "(([31] * 3) + in0) >= 100");
+
+        let witness = executor.find_output(65).unwrap();
+        assert_eq!(3 * witness.cells[&31] + witness.inputs[0], 65);
+        program[31] = witness.cells[&31];
+        assert_eq!(outputs(&program, &witness.inputs), vec![65]);
+        assert_eq!(executor.find_output(100), None);
+    }
+
+    #[test]
+    fn negative_symbolic_cell() {
+        let mut executor = Executor::new(&parse_program("99"));
+        executor.add_symbolic_cell(-1, 0..=1);
+        let ends: Vec<PathEnd> = executor.explore().into_iter().map(|path| path.end).collect();
+        assert_eq!(ends, vec![PathEnd::Halted]);
+    }
+
+    #[test]
+    fn negated_conditions() {
+        let x = Expr::Symbol(Symbol::Input(0));
+        let condition = Expr::equals(Expr::less_than(x.clone(), Expr::Const(5)), Expr::Const(0));
+        assert_eq!(Constraint::new(condition, true).to_string(), "in0 >= 5");
+    }
+
+    #[test]
+    fn limits() {
+        // reads inputs until it gets a 0, then jumps to the last one
+        let mut executor = Executor::new(&parse_program("3,100,1005,100,0,109,1,2105,1,99,99"));
+        executor.set_max_inputs(3);
+        let ends: Vec<PathEnd> = executor.explore().into_iter().map(|path| path.end).collect();
+        assert_eq!(ends, vec![
+            PathEnd::Unsupported { ip: 7, reason: "symbolic jump target" },
+            PathEnd::Unsupported { ip: 7, reason: "symbolic jump target" },
+            PathEnd::Unsupported { ip: 7, reason: "symbolic jump target" },
+            PathEnd::InputLimit
+        ]);
+    }
+}
@@ -0,0 +1,282 @@
+//! Opt-in execution tracing, see `Machine::run_traced`.
+//!
+//! A trace can be written as JSON lines for reading or grepping, or as fixed size binary records
+//! for comparing long runs between two versions of the interpreter. `Profile` only counts.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use crate::decode::{decode, Instruction, Opcode};
+
+/// One executed instruction, as seen before it ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceStep {
+    pub ip: i64,
+    /// The raw opcode word.
+    pub opcode: i64,
+    pub instruction: Instruction,
+    /// Raw parameter words. Only the first `parameter_count` are meaningful, the rest are 0.
+    pub operands: [i64; 3],
+    pub relative_base: i64,
+    /// Address and new value of the cell the instruction wrote, if it writes one.
+    pub write: Option<(i64, i64)>
+}
+
+impl TraceStep {
+    pub fn operands(&self) -> &[i64] {
+        &self.operands[..self.instruction.op.parameter_count()]
+    }
+
+    /// Formats the step as a single line JSON object.
+    pub fn to_json(&self) -> String {
+        let join = |values: &mut dyn Iterator<Item = i64>| values.map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+
+        let mut s = String::new();
+        let _ = write!(
+            s,
+            "{{\"ip\":{},\"opcode\":{},\"op\":\"{}\",\"modes\":[{}],\"operands\":[{}],\"rb\":{},\"write\":",
+            self.ip,
+            self.opcode,
+            self.instruction.op.mnemonic(),
+            join(&mut self.instruction.modes[..self.operands().len()].iter().map(|m| m.digit())),
+            join(&mut self.operands().iter().copied()),
+            self.relative_base
+        );
+        match self.write {
+            Some((address, value)) => { let _ = write!(s, "{{\"address\":{},\"value\":{}}}}}", address, value); },
+            None => s.push_str("null}")
+        }
+        s
+    }
+}
+
+/// Receives every instruction a traced machine executes.
+pub trait Tracer {
+    fn trace(&mut self, step: &TraceStep);
+
+    /// Called when `Machine::run_traced` returns, whatever it returns, with the ip the machine
+    /// stopped at.
+    fn stopped(&mut self, _ip: i64) {}
+}
+
+impl<F: FnMut(&TraceStep)> Tracer for F {
+    fn trace(&mut self, step: &TraceStep) {
+        self(step)
+    }
+}
+
+/// Writes one JSON object per step.
+///
+/// Tracing can't fail, so the first write error is kept and returned by `finish`.
+pub struct JsonlTracer<W: Write> {
+    writer: W,
+    error: Option<io::Error>
+}
+
+impl<W: Write> JsonlTracer<W> {
+    pub fn new(writer: W) -> Self {
+        JsonlTracer { writer, error: None }
+    }
+
+    /// Flushes the writer and hands it back, or returns the first error writing the trace.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush().map(|_| self.writer)
+        }
+    }
+}
+
+impl<W: Write> Tracer for JsonlTracer<W> {
+    fn trace(&mut self, step: &TraceStep) {
+        if self.error.is_none() {
+            self.error = writeln!(self.writer, "{}", step.to_json()).err();
+        }
+    }
+}
+
+/// Number of little endian `i64`s in a binary trace record: ip, opcode, relative base, three
+/// operands, written address (-1 if nothing was written) and written value.
+const RECORD_WORDS: usize = 8;
+
+/// Writes fixed size binary records, see `read_binary_trace`.
+pub struct BinaryTracer<W: Write> {
+    writer: W,
+    error: Option<io::Error>
+}
+
+impl<W: Write> BinaryTracer<W> {
+    pub fn new(writer: W) -> Self {
+        BinaryTracer { writer, error: None }
+    }
+
+    /// Flushes the writer and hands it back, or returns the first error writing the trace.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush().map(|_| self.writer)
+        }
+    }
+}
+
+impl<W: Write> Tracer for BinaryTracer<W> {
+    fn trace(&mut self, step: &TraceStep) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let (write_address, write_value) = step.write.unwrap_or((-1, 0));
+        let words: [i64; RECORD_WORDS] = [
+            step.ip,
+            step.opcode,
+            step.relative_base,
+            step.operands[0],
+            step.operands[1],
+            step.operands[2],
+            write_address,
+            write_value
+        ];
+        let mut record = [0u8; RECORD_WORDS * 8];
+        for (chunk, word) in record.chunks_mut(8).zip(words.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        self.error = self.writer.write_all(&record).err();
+    }
+}
+
+/// Reads back a trace written by `BinaryTracer`.
+pub fn read_binary_trace<R: Read>(mut reader: R) -> io::Result<Vec<TraceStep>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() % (RECORD_WORDS * 8) != 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "trace ends in the middle of a record"));
+    }
+
+    bytes.chunks(RECORD_WORDS * 8).map(|record| {
+        let mut words = [0i64; RECORD_WORDS];
+        for (word, chunk) in words.iter_mut().zip(record.chunks(8)) {
+            let mut le_bytes = [0u8; 8];
+            le_bytes.copy_from_slice(chunk);
+            *word = i64::from_le_bytes(le_bytes);
+        }
+
+        let instruction = decode(words[1])
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("bad opcode {} in trace", words[1])))?;
+        Ok(TraceStep {
+            ip: words[0],
+            opcode: words[1],
+            instruction,
+            operands: [words[3], words[4], words[5]],
+            relative_base: words[2],
+            write: if words[6] < 0 { None } else { Some((words[6], words[7])) }
+        })
+    }).collect()
+}
+
+/// Counts executed instructions per opcode and per address.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub steps: u64,
+    pub by_opcode: HashMap<Opcode, u64>,
+    pub by_address: HashMap<i64, u64>
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Profile::default()
+    }
+
+    /// The `n` most executed addresses, most executed first.
+    pub fn hottest_addresses(&self, n: usize) -> Vec<(i64, u64)> {
+        let mut addresses: Vec<(i64, u64)> = self.by_address.iter().map(|(a, c)| (*a, *c)).collect();
+        addresses.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        addresses.truncate(n);
+        addresses
+    }
+
+    /// A plain text report of the opcode counts and the `top` hottest addresses.
+    pub fn summary(&self, top: usize) -> String {
+        let mut s = String::new();
+        let _ = writeln!(s, "{} instructions", self.steps);
+
+        let mut opcodes: Vec<(Opcode, u64)> = self.by_opcode.iter().map(|(o, c)| (*o, *c)).collect();
+        opcodes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.code().cmp(&b.0.code())));
+        for (op, count) in opcodes {
+            let _ = writeln!(s, "  {:<4}{:>12} {:>6.2}%", op.mnemonic(), count, 100.0 * count as f64 / self.steps as f64);
+        }
+
+        let _ = writeln!(s, "hottest addresses");
+        for (address, count) in self.hottest_addresses(top) {
+            let _ = writeln!(s, "  {:>5}{:>12}", address, count);
+        }
+        s
+    }
+}
+
+impl Tracer for Profile {
+    fn trace(&mut self, step: &TraceStep) {
+        self.steps += 1;
+        *self.by_opcode.entry(step.instruction.op).or_insert(0) += 1;
+        *self.by_address.entry(step.ip).or_insert(0) += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trace::{read_binary_trace, BinaryTracer, JsonlTracer, Profile, TraceStep, Tracer};
+    use crate::{parse_program, Machine, Opcode, StopReason};
+
+    // adds the input to itself through a relative-mode store, outputs it and halts
+    const PROGRAM: &str = "109,20,203,0,22201,0,0,1,204,1,99";
+
+    #[test]
+    fn records_writes_and_operands() {
+        let mut m = Machine::new(&parse_program(PROGRAM));
+        let mut steps: Vec<TraceStep> = Vec::new();
+        let mut record = |step: &TraceStep| steps.push(*step);
+
+        assert_eq!(m.run_traced(&mut record), Ok(StopReason::NeedInput));
+        m.push_input(21);
+        assert_eq!(m.run_traced(&mut record), Ok(StopReason::Output(42)));
+        assert_eq!(m.run_traced(&mut record), Ok(StopReason::Halted));
+
+        let ops: Vec<Opcode> = steps.iter().map(|s| s.instruction.op).collect();
+        assert_eq!(ops, vec![Opcode::AdjustRelativeBase, Opcode::Input, Opcode::Add, Opcode::Output, Opcode::Halt]);
+        assert_eq!(steps[1].write, Some((20, 21)));
+        assert_eq!(steps[2].operands(), &[0, 0, 1]);
+        assert_eq!(steps[2].write, Some((21, 42)));
+        assert_eq!(steps[3].relative_base, 20);
+        assert_eq!(
+            steps[2].to_json(),
+            r#"{"ip":4,"opcode":22201,"op":"ADD","modes":[2,2,2],"operands":[0,0,1],"rb":20,"write":{"address":21,"value":42}}"#
+        );
+        assert_eq!(steps[4].to_json(), r#"{"ip":10,"opcode":99,"op":"HLT","modes":[],"operands":[],"rb":20,"write":null}"#);
+    }
+
+    fn run_to_halt(tracer: &mut dyn Tracer) {
+        let mut m = Machine::new(&parse_program(PROGRAM));
+        m.push_input(21);
+        while m.run_traced(tracer).unwrap() != StopReason::Halted {}
+    }
+
+    #[test]
+    fn writers_and_profile() {
+        let mut steps = Vec::new();
+        run_to_halt(&mut |step: &TraceStep| steps.push(*step));
+
+        let mut binary = BinaryTracer::new(Vec::new());
+        run_to_halt(&mut binary);
+        assert_eq!(read_binary_trace(&binary.finish().unwrap()[..]).unwrap(), steps);
+
+        let mut jsonl = JsonlTracer::new(Vec::new());
+        run_to_halt(&mut jsonl);
+        let json = String::from_utf8(jsonl.finish().unwrap()).unwrap();
+        assert_eq!(json.lines().collect::<Vec<_>>(), steps.iter().map(|s| s.to_json()).collect::<Vec<_>>());
+
+        let mut profile = Profile::new();
+        run_to_halt(&mut profile);
+        assert_eq!(profile.steps, 5);
+        assert_eq!(profile.by_opcode[&Opcode::Add], 1);
+        assert_eq!(profile.hottest_addresses(2), vec![(0, 1), (2, 1)]);
+    }
+}
@@ -0,0 +1,344 @@
+//! Ahead-of-time translation of a program image to Rust source, see `intcode2rs`.
+//!
+//! Control flow is followed from address 0 to find every instruction reachable through jumps
+//! with immediate targets. Each jump target starts a basic block, and the blocks become the arms
+//! of a `match` on the next address, which doubles as the computed goto for jumps whose target is
+//! only known at run time.
+//!
+//! Programs built with the usual call idiom push a return address as an immediate before jumping
+//! away, so the address after an unconditional jump also starts a block if some instruction uses
+//! it as an immediate. Parameter words the program overwrites, the common way to address memory
+//! indirectly, are read from memory when the instruction runs. Everything else falls back to the
+//! interpreter through `aot::Cpu`:
+//!
+//! * jumps to an address that isn't the start of a block,
+//! * words that don't decode or would make the interpreter fail,
+//! * writes to other translated cells, after which the translation no longer matches the program.
+//!
+//! Fallbacks only cost speed, a translated program always produces the interpreter's I/O.
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+use crate::decode::Opcode;
+use crate::disasm::{disassemble_at, Item, Operand};
+
+struct Decoded {
+    op: Opcode,
+    operands: Vec<Operand>
+}
+
+fn decoded_at(program: &[i64], address: usize) -> Option<Decoded> {
+    match disassemble_at(program, address).item {
+        Item::Instruction { op, operands } => Some(Decoded { op, operands }),
+        Item::Data(_) => None
+    }
+}
+
+/// Whether a jump is always taken, judging by an immediate condition.
+fn always_taken(instruction: &Decoded) -> bool {
+    match (instruction.op, instruction.operands[0]) {
+        (Opcode::JumpIfTrue, Operand::Immediate(condition)) => condition != 0,
+        (Opcode::JumpIfFalse, Operand::Immediate(condition)) => condition == 0,
+        _ => false
+    }
+}
+
+fn jump_target(program: &[i64], instruction: &Decoded) -> Option<usize> {
+    match instruction.operands[1] {
+        Operand::Immediate(target) if target >= 0 && (target as usize) < program.len() => Some(target as usize),
+        _ => None
+    }
+}
+
+struct Analysis {
+    /// Addresses that start a basic block.
+    leaders: BTreeSet<usize>,
+    /// Cells of reachable instructions, except for `patched` ones.
+    code: BTreeSet<usize>,
+    /// Parameter words the program overwrites through a position-mode write, the usual way to get
+    /// indirect addressing. Translated code reads these from memory.
+    patched: BTreeSet<usize>
+}
+
+fn analyse(program: &[i64]) -> Analysis {
+    let mut leaders = BTreeSet::new();
+    let mut visited = HashSet::new();
+    let mut code = BTreeSet::new();
+    let mut opcodes = HashSet::new();
+    let mut position_writes = HashSet::new();
+    // addresses after unconditional jumps, only blocks if something refers to them
+    let mut after_jumps = BTreeSet::new();
+    let mut immediates = HashSet::new();
+
+    let mut worklist = vec![0];
+    leaders.insert(0);
+    loop {
+        while let Some(start) = worklist.pop() {
+            let mut address = start;
+            while address < program.len() && visited.insert(address) {
+                let instruction = match decoded_at(program, address) {
+                    Some(instruction) => instruction,
+                    None => break
+                };
+                let next = address + instruction.operands.len() + 1;
+                code.extend(address..next);
+                opcodes.insert(address);
+                if let Some(parameter) = instruction.op.write_parameter() {
+                    if let Operand::Position(target) = instruction.operands[parameter - 1] {
+                        position_writes.insert(target);
+                    }
+                }
+                for operand in &instruction.operands {
+                    if let Operand::Immediate(value) = operand {
+                        immediates.insert(*value);
+                    }
+                }
+
+                match instruction.op {
+                    Opcode::Halt => break,
+                    Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                        if let Some(target) = jump_target(program, &instruction) {
+                            if leaders.insert(target) {
+                                worklist.push(target);
+                            }
+                        }
+                        if always_taken(&instruction) {
+                            after_jumps.insert(next);
+                        } else if leaders.insert(next) {
+                            worklist.push(next);
+                        }
+                        break;
+                    },
+                    _ => address = next
+                }
+            }
+        }
+
+        let returns: Vec<usize> = after_jumps.iter()
+            .copied()
+            .filter(|a| !leaders.contains(a) && immediates.contains(&(*a as i64)))
+            .collect();
+        if returns.is_empty() {
+            break;
+        }
+        for address in returns {
+            leaders.insert(address);
+            worklist.push(address);
+        }
+    }
+
+    let patched: BTreeSet<usize> = code.iter()
+        .copied()
+        .filter(|a| position_writes.contains(&(*a as i64)) && !opcodes.contains(a))
+        .collect();
+    let code = code.difference(&patched).copied().collect();
+    Analysis { leaders, code, patched }
+}
+
+fn literal(value: i64) -> String {
+    if value < 0 { format!("({})", value) } else { value.to_string() }
+}
+
+/// Expression for the raw parameter word at `address`, `value` unless the program patches it.
+fn parameter_word(address: usize, value: i64, analysis: &Analysis) -> String {
+    if analysis.patched.contains(&address) {
+        format!("cpu.word({})", address)
+    } else {
+        literal(value)
+    }
+}
+
+/// Expression reading the `parameter`th operand of the instruction at `ip`.
+fn read(ip: usize, parameter: usize, operand: Operand, analysis: &Analysis) -> String {
+    let word = parameter_word(ip + parameter, operand.value(), analysis);
+    match operand {
+        Operand::Immediate(_) => word,
+        Operand::Position(_) => format!("cpu.get({}, {}, {})?", ip, parameter, word),
+        Operand::Relative(_) => format!("cpu.get({}, {}, cpu.relative({}, {})?)?", ip, parameter, ip, word)
+    }
+}
+
+/// Statement writing `value` through the `parameter`th operand, handing over to the interpreter at
+/// `next` if that changed translated code.
+fn write(ip: usize, parameter: usize, operand: Operand, value: &str, next: usize, analysis: &Analysis) -> String {
+    let word = parameter_word(ip + parameter, operand.value(), analysis);
+    let address = match operand {
+        Operand::Position(address) if !analysis.patched.contains(&(ip + parameter)) => {
+            if address >= 0 && analysis.code.contains(&(address as usize)) {
+                return format!(
+                    "cpu.set({}, {}, {}, {})?;\n                    return cpu.interpret({}, io);",
+                    ip, parameter, word, value, next
+                );
+            }
+            return format!("cpu.set({}, {}, {}, {})?;", ip, parameter, word, value);
+        },
+        Operand::Position(_) => word,
+        Operand::Relative(_) => format!("cpu.relative({}, {})?", ip, word),
+        Operand::Immediate(_) => unreachable!("disassemble_at doesn't decode immediate writes")
+    };
+    format!(
+        "if cpu.set_checked({}, {}, {}, {})? {{\n                        return cpu.interpret({}, io);\n                    }}",
+        ip, parameter, address, value, next
+    )
+}
+
+fn translate_instruction(out: &mut String, ip: usize, instruction: &Decoded, analysis: &Analysis) {
+    let next = ip + instruction.operands.len() + 1;
+    let operand = |i: usize| instruction.operands[i];
+
+    out.push_str("                {\n");
+    match instruction.op {
+        Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => {
+            let _ = writeln!(out, "                    let a = {};", read(ip, 1, operand(0), analysis));
+            let _ = writeln!(out, "                    let b = {};", read(ip, 2, operand(1), analysis));
+            let value = match instruction.op {
+                Opcode::Add => {
+                    let _ = writeln!(out, "                    let c = cpu.add({}, a, b)?;", ip);
+                    "c"
+                },
+                Opcode::Multiply => {
+                    let _ = writeln!(out, "                    let c = cpu.mul({}, a, b)?;", ip);
+                    "c"
+                },
+                Opcode::LessThan => "(a < b) as i64",
+                _ => "(a == b) as i64"
+            };
+            let _ = writeln!(out, "                    {}", write(ip, 3, operand(2), value, next, analysis));
+        },
+        Opcode::Input => {
+            let _ = writeln!(out, "                    let a = cpu.input({}, io)?;", ip);
+            let _ = writeln!(out, "                    {}", write(ip, 1, operand(0), "a", next, analysis));
+        },
+        Opcode::Output => {
+            let _ = writeln!(out, "                    io.output({});", read(ip, 1, operand(0), analysis));
+        },
+        Opcode::AdjustRelativeBase => {
+            let _ = writeln!(out, "                    cpu.adjust_rb({}, {})?;", ip, read(ip, 1, operand(0), analysis));
+        },
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+            // both parameters are read before jumping, like the interpreter does
+            let _ = writeln!(out, "                    let a = {};", read(ip, 1, operand(0), analysis));
+            let _ = writeln!(out, "                    let b = {};", read(ip, 2, operand(1), analysis));
+            let comparison = if instruction.op == Opcode::JumpIfTrue { "!=" } else { "==" };
+            let _ = writeln!(out, "                    if a {} 0 {{\n                        block = b;\n                        continue;\n                    }}", comparison);
+        },
+        Opcode::Halt => out.push_str("                    return Ok(());\n")
+    }
+    out.push_str("                }\n");
+}
+
+/// Translates a program image into Rust source defining
+/// `pub fn <name>(io: &mut dyn intcode::aot::Io) -> Result<(), intcode::aot::AotError>`.
+pub fn translate(program: &[i64], name: &str) -> String {
+    let analysis = analyse(program);
+    let constant = name.to_uppercase();
+
+    let mut out = String::new();
+    let _ = writeln!(out, "// Generated by intcode2rs from a {} word program, {} blocks. Don't edit.", program.len(), analysis.leaders.len());
+    out.push('\n');
+    out.push_str("#[allow(unused_mut, unused_parens, unreachable_code, clippy::all)]\n");
+    let _ = writeln!(out, "pub fn {}(io: &mut dyn intcode::aot::Io) -> Result<(), intcode::aot::AotError> {{", name);
+    let _ = writeln!(out, "    let mut cpu = intcode::aot::Cpu::new(&{}_PROGRAM, &{}_CODE);", constant, constant);
+    out.push_str("    let mut block: i64 = 0;\n");
+    out.push_str("    loop {\n");
+    out.push_str("        match block {\n");
+
+    for leader in &analysis.leaders {
+        let _ = writeln!(out, "            {} => {{", leader);
+        let mut address = *leader;
+        loop {
+            let instruction = match decoded_at(program, address) {
+                Some(instruction) => instruction,
+                None => {
+                    let _ = writeln!(out, "                return cpu.interpret({}, io);", address);
+                    break;
+                }
+            };
+            let _ = writeln!(out, "                // {}", disassemble_at(program, address).to_string().trim_start());
+            translate_instruction(&mut out, address, &instruction, &analysis);
+
+            let next = address + instruction.operands.len() + 1;
+            match instruction.op {
+                Opcode::Halt => break,
+                Opcode::JumpIfTrue | Opcode::JumpIfFalse if always_taken(&instruction) => {
+                    if analysis.patched.contains(&(address + 1)) {
+                        // the condition may not hold after all, nothing after the jump was translated
+                        let _ = writeln!(out, "                return cpu.interpret({}, io);", next);
+                    }
+                    break;
+                },
+                _ => {}
+            }
+            if analysis.leaders.contains(&next) {
+                let _ = writeln!(out, "                block = {};", next);
+                break;
+            }
+            address = next;
+        }
+        out.push_str("            },\n");
+    }
+
+    out.push_str("            _ => return cpu.interpret(block, io)\n");
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("}\n\n");
+
+    // the compiled code as address ranges
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for address in analysis.code {
+        match ranges.last_mut() {
+            Some(range) if range.1 == address => range.1 += 1,
+            _ => ranges.push((address, address + 1))
+        }
+    }
+    let _ = writeln!(out, "pub static {}_CODE: [(usize, usize); {}] = [", constant, ranges.len());
+    for chunk in ranges.chunks(8) {
+        let line: Vec<String> = chunk.iter().map(|(start, end)| format!("({}, {})", start, end)).collect();
+        let _ = writeln!(out, "    {},", line.join(", "));
+    }
+    out.push_str("];\n\n");
+
+    let _ = writeln!(out, "pub static {}_PROGRAM: [i64; {}] = [", constant, program.len());
+    for chunk in program.chunks(16) {
+        let line: Vec<String> = chunk.iter().map(|word| word.to_string()).collect();
+        let _ = writeln!(out, "    {},", line.join(", "));
+    }
+    out.push_str("];\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::translate::{analyse, translate};
+    use crate::parse_program;
+
+    #[test]
+    fn blocks() {
+        // 0: JZ [12], #10   3: OUT #1   5: JNZ #1, #0   8: OUT #2   10: OUT #3   12: HLT
+        // the address after the unconditional jump is only a block if something refers to it
+        let program = parse_program("1006,12,10,104,1,1105,1,0,104,2,104,3,99");
+        let analysis = analyse(&program);
+        assert_eq!(analysis.leaders.into_iter().collect::<Vec<_>>(), vec![0, 3, 10]);
+        assert!(analysis.code.contains(&12) && !analysis.code.contains(&8));
+
+        // same with `OUT #8` at 3
+        let program = parse_program("1006,12,10,104,8,1105,1,0,104,2,104,3,99");
+        assert_eq!(analyse(&program).leaders.into_iter().collect::<Vec<_>>(), vec![0, 3, 8, 10]);
+    }
+
+    #[test]
+    fn source() {
+        let source = translate(&parse_program("1101,2,-3,5,99,0"), "sum");
+        assert!(source.contains("pub fn sum(io: &mut dyn intcode::aot::Io)"));
+        assert!(source.contains("// 0: ADD #2, #-3, [5]"));
+        assert!(source.contains("let b = (-3);"));
+        assert!(source.contains("cpu.set(0, 3, 5, c)?;"));
+        assert!(source.contains("pub static SUM_CODE: [(usize, usize); 1] = [\n    (0, 5),\n];"));
+
+        // 0: ADD #7, #0, [5]   4: OUT [0]   6: HLT, the write patches the operand at 5
+        let source = translate(&parse_program("1101,7,0,5,4,0,99"), "patched");
+        assert!(source.contains("cpu.set(0, 3, 5, c)?;"));
+        assert!(source.contains("io.output(cpu.get(4, 1, cpu.word(5))?);"));
+        assert!(source.contains("(0, 5), (6, 7),"));
+    }
+}
@@ -0,0 +1,76 @@
+//! Values a `Machine` computes with.
+//!
+//! Addresses, the instruction pointer and the relative base stay `i64` whatever the word type,
+//! only memory, inputs and outputs use it.
+
+use std::convert::TryFrom;
+use std::fmt;
+use num_bigint::{BigInt, Sign};
+
+/// A memory cell value.
+///
+/// Arithmetic is checked, a result that doesn't fit the type is an overflow rather than wrapping
+/// around.
+pub trait Word: Clone + Default + PartialEq + PartialOrd + fmt::Debug + fmt::Display {
+    fn from_i64(value: i64) -> Self;
+
+    /// The value as an address or opcode. Values out of the `i64` range are clamped to it, which
+    /// still makes them out of range as an address.
+    fn saturating_i64(&self) -> i64;
+
+    fn try_add(&self, other: &Self) -> Option<Self>;
+
+    fn try_mul(&self, other: &Self) -> Option<Self>;
+
+    fn is_zero(&self) -> bool;
+}
+
+impl Word for i64 {
+    #[inline]
+    fn from_i64(value: i64) -> Self {
+        value
+    }
+
+    #[inline]
+    fn saturating_i64(&self) -> i64 {
+        *self
+    }
+
+    #[inline]
+    fn try_add(&self, other: &Self) -> Option<Self> {
+        self.checked_add(*other)
+    }
+
+    #[inline]
+    fn try_mul(&self, other: &Self) -> Option<Self> {
+        self.checked_mul(*other)
+    }
+
+    #[inline]
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+}
+
+/// Arbitrary precision, never overflows.
+impl Word for BigInt {
+    fn from_i64(value: i64) -> Self {
+        BigInt::from(value)
+    }
+
+    fn saturating_i64(&self) -> i64 {
+        i64::try_from(self).unwrap_or(if self.sign() == Sign::Minus { i64::MIN } else { i64::MAX })
+    }
+
+    fn try_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn try_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.sign() == Sign::NoSign
+    }
+}